@@ -0,0 +1,398 @@
+// Keyframe animation. Objects, lights and the camera each carry their own tracks, so an
+// object takes its animation along when it is deleted, undone or saved.
+// A track only moves the property it belongs to once it has a keyframe, everything
+// without any stays wherever it was put.
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::math::*;
+use crate::scene::{Light, Mesh, Scene};
+
+// Keyframes closer together than this, in seconds, count as the same one
+pub const KEY_TOLERANCE: f32 = 1e-3;
+
+// How values get from one keyframe to the next
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Interpolation {
+    // Holds each value until the next keyframe
+    Step,
+    #[default]
+    Linear,
+    // Smooth curves through the keyframes (Catmull-Rom)
+    Cubic,
+}
+
+// Values that can be keyed and blended between keyframes
+pub trait Keyable: Copy {
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+    // Curve from `b` to `c`, with `a` and `d` the keyframes either side of them
+    fn cubic(a: Self, b: Self, c: Self, d: Self, t: f32) -> Self;
+}
+
+impl Keyable for f32 {
+    fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        a + (b - a) * t
+    }
+
+    fn cubic(a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+        let t2 = t * t;
+        let t3 = t2 * t;
+        0.5 * (2.0 * b + (c - a) * t + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2 + (3.0 * b - a - 3.0 * c + d) * t3)
+    }
+}
+
+impl Keyable for [f32; 3] {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        [0, 1, 2].map(|i| f32::lerp(a[i], b[i], t))
+    }
+
+    fn cubic(a: Self, b: Self, c: Self, d: Self, t: f32) -> Self {
+        [0, 1, 2].map(|i| f32::cubic(a[i], b[i], c[i], d[i], t))
+    }
+}
+
+// Quaternions. q and -q are the same rotation, each one gets flipped to the side of the one
+// before it so blending takes the short way around.
+impl Keyable for Quat {
+    fn lerp(a: Quat, b: Quat, t: f32) -> Quat {
+        let b = same_side(a, b);
+        let cos = quat_dot(a, b).min(1.0);
+        // Nearly the same rotation, where slerp would divide by almost nothing
+        if cos > 0.9995 {
+            return quat_normalize([0, 1, 2, 3].map(|i| f32::lerp(a[i], b[i], t)));
+        }
+        let angle = cos.acos();
+        let (weight_a, weight_b) = (((1.0 - t) * angle).sin() / angle.sin(), (t * angle).sin() / angle.sin());
+        [0, 1, 2, 3].map(|i| a[i] * weight_a + b[i] * weight_b)
+    }
+
+    fn cubic(a: Quat, b: Quat, c: Quat, d: Quat, t: f32) -> Quat {
+        let a = same_side(b, a);
+        let c = same_side(b, c);
+        let d = same_side(c, d);
+        quat_normalize([0, 1, 2, 3].map(|i| f32::cubic(a[i], b[i], c[i], d[i], t)))
+    }
+}
+
+fn quat_dot(a: Quat, b: Quat) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+}
+
+fn same_side(reference: Quat, q: Quat) -> Quat {
+    if quat_dot(reference, q) < 0.0 {
+        q.map(|component| -component)
+    } else {
+        q
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe<T> {
+    // Seconds from the start
+    pub time: f32,
+    pub value: T,
+}
+
+// Keyframes of one property, kept in time order
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Track<T> {
+    pub keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Track { keyframes: vec![] }
+    }
+}
+
+impl<T: Keyable> Track<T> {
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    // The value at `time`. Before the first keyframe and after the last one it holds still.
+    pub fn sample(&self, time: f32, interpolation: Interpolation) -> Option<T> {
+        let keyframes = &self.keyframes;
+        let first = keyframes.first()?;
+        let last = keyframes.last()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+        if time >= last.time {
+            return Some(last.value);
+        }
+
+        // Somewhere between two keyframes, `next` is at least 1
+        let next = keyframes.partition_point(|keyframe| keyframe.time <= time);
+        let (from, to) = (&keyframes[next - 1], &keyframes[next]);
+        let t = (time - from.time) / (to.time - from.time);
+
+        Some(match interpolation {
+            Interpolation::Step => from.value,
+            Interpolation::Linear => T::lerp(from.value, to.value, t),
+            Interpolation::Cubic => {
+                // The ends have nothing further out, so they lean on themselves
+                let before = keyframes[next.saturating_sub(2)].value;
+                let after = keyframes[(next + 1).min(keyframes.len() - 1)].value;
+                T::cubic(before, from.value, to.value, after, t)
+            }
+        })
+    }
+
+    // Adds a keyframe, or changes the one already at that time
+    pub fn insert(&mut self, time: f32, value: T) {
+        match self.keyframes.iter_mut().find(|keyframe| (keyframe.time - time).abs() < KEY_TOLERANCE) {
+            Some(keyframe) => keyframe.value = value,
+            None => {
+                let index = self.keyframes.partition_point(|keyframe| keyframe.time < time);
+                self.keyframes.insert(index, Keyframe { time, value });
+            }
+        }
+    }
+
+    // Returns whether there was a keyframe at that time
+    pub fn remove(&mut self, time: f32) -> bool {
+        let count = self.keyframes.len();
+        self.keyframes.retain(|keyframe| (keyframe.time - time).abs() >= KEY_TOLERANCE);
+        self.keyframes.len() != count
+    }
+
+    // Puts keyframes read from a file back in time order, `sample` relies on it.
+    // False when one of the times isn't a number.
+    pub fn sort(&mut self) -> bool {
+        if self.keyframes.iter().any(|keyframe| !keyframe.time.is_finite()) {
+            return false;
+        }
+        self.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        true
+    }
+
+    fn times(&self) -> impl Iterator<Item = f32> + '_ {
+        self.keyframes.iter().map(|keyframe| keyframe.time)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectAnimation {
+    pub interpolation: Interpolation,
+    pub translation: Track<[f32; 3]>,
+    pub rotation: Track<Quat>,
+    pub scale: Track<[f32; 3]>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightAnimation {
+    pub interpolation: Interpolation,
+    pub position: Track<[f32; 3]>,
+    pub direction: Track<[f32; 3]>,
+    pub color: Track<[f32; 3]>,
+    pub intensity: Track<f32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraAnimation {
+    pub interpolation: Interpolation,
+    pub position: Track<[f32; 3]>,
+    // Euler angles in degrees like `Camera::rotation`, so a turn past 180 degrees keys fine
+    pub rotation: Track<[f32; 3]>,
+    pub fov: Track<f32>,
+}
+
+// Anything with keyframes of its own. Keying always keys every property, so all of them
+// stay where they are when the keyframe is played back.
+pub trait Animated {
+    // Moves to where the keyframes say it is at `time`
+    fn animate(&mut self, time: f32);
+    // Keys everything at `time` as it is now
+    fn set_key(&mut self, time: f32);
+    // Returns whether there was a keyframe at that time
+    fn remove_key(&mut self, time: f32) -> bool;
+    fn clear_keys(&mut self);
+    // Every time something is keyed at, sorted, without duplicates
+    fn key_times(&self) -> Vec<f32>;
+    fn interpolation_mut(&mut self) -> &mut Interpolation;
+    // Sorts every track, see `Track::sort`
+    fn sort_keys(&mut self) -> bool;
+}
+
+impl Animated for Mesh {
+    fn animate(&mut self, time: f32) {
+        let animation = &self.animation;
+        let interpolation = animation.interpolation;
+        if let Some(translation) = animation.translation.sample(time, interpolation) {
+            self.transform.translation = translation;
+        }
+        if let Some(rotation) = animation.rotation.sample(time, interpolation) {
+            self.transform.rotation = rotation;
+        }
+        if let Some(scale) = animation.scale.sample(time, interpolation) {
+            self.transform.scale = scale;
+        }
+    }
+
+    fn set_key(&mut self, time: f32) {
+        let animation = &mut self.animation;
+        animation.translation.insert(time, self.transform.translation);
+        animation.rotation.insert(time, self.transform.rotation);
+        animation.scale.insert(time, self.transform.scale);
+    }
+
+    fn remove_key(&mut self, time: f32) -> bool {
+        let animation = &mut self.animation;
+        let translation = animation.translation.remove(time);
+        let rotation = animation.rotation.remove(time);
+        let scale = animation.scale.remove(time);
+        translation || rotation || scale
+    }
+
+    fn clear_keys(&mut self) {
+        self.animation = ObjectAnimation { interpolation: self.animation.interpolation, ..Default::default() };
+    }
+
+    fn key_times(&self) -> Vec<f32> {
+        let animation = &self.animation;
+        merge_times(animation.translation.times().chain(animation.rotation.times()).chain(animation.scale.times()))
+    }
+
+    fn interpolation_mut(&mut self) -> &mut Interpolation {
+        &mut self.animation.interpolation
+    }
+
+    fn sort_keys(&mut self) -> bool {
+        let animation = &mut self.animation;
+        animation.translation.sort() && animation.rotation.sort() && animation.scale.sort()
+    }
+}
+
+impl Animated for Light {
+    fn animate(&mut self, time: f32) {
+        let animation = &self.animation;
+        let interpolation = animation.interpolation;
+        if let Some(position) = animation.position.sample(time, interpolation) {
+            self.position = position;
+        }
+        if let Some(direction) = animation.direction.sample(time, interpolation) {
+            self.direction = direction;
+        }
+        if let Some(color) = animation.color.sample(time, interpolation) {
+            self.color = color;
+        }
+        if let Some(intensity) = animation.intensity.sample(time, interpolation) {
+            self.intensity = intensity;
+        }
+    }
+
+    fn set_key(&mut self, time: f32) {
+        let animation = &mut self.animation;
+        animation.position.insert(time, self.position);
+        animation.direction.insert(time, self.direction);
+        animation.color.insert(time, self.color);
+        animation.intensity.insert(time, self.intensity);
+    }
+
+    fn remove_key(&mut self, time: f32) -> bool {
+        let animation = &mut self.animation;
+        let position = animation.position.remove(time);
+        let direction = animation.direction.remove(time);
+        let color = animation.color.remove(time);
+        let intensity = animation.intensity.remove(time);
+        position || direction || color || intensity
+    }
+
+    fn clear_keys(&mut self) {
+        self.animation = LightAnimation { interpolation: self.animation.interpolation, ..Default::default() };
+    }
+
+    fn key_times(&self) -> Vec<f32> {
+        let animation = &self.animation;
+        merge_times(
+            animation
+                .position
+                .times()
+                .chain(animation.direction.times())
+                .chain(animation.color.times())
+                .chain(animation.intensity.times()),
+        )
+    }
+
+    fn interpolation_mut(&mut self) -> &mut Interpolation {
+        &mut self.animation.interpolation
+    }
+
+    fn sort_keys(&mut self) -> bool {
+        let animation = &mut self.animation;
+        animation.position.sort() && animation.direction.sort() && animation.color.sort() && animation.intensity.sort()
+    }
+}
+
+impl Animated for Camera {
+    fn animate(&mut self, time: f32) {
+        let animation = &self.animation;
+        let interpolation = animation.interpolation;
+        if let Some(position) = animation.position.sample(time, interpolation) {
+            self.position = position;
+        }
+        if let Some(rotation) = animation.rotation.sample(time, interpolation) {
+            self.rotation = rotation;
+        }
+        if let Some(fov) = animation.fov.sample(time, interpolation) {
+            self.fov = fov;
+        }
+    }
+
+    fn set_key(&mut self, time: f32) {
+        let animation = &mut self.animation;
+        animation.position.insert(time, self.position);
+        animation.rotation.insert(time, self.rotation);
+        animation.fov.insert(time, self.fov);
+    }
+
+    fn remove_key(&mut self, time: f32) -> bool {
+        let animation = &mut self.animation;
+        let position = animation.position.remove(time);
+        let rotation = animation.rotation.remove(time);
+        let fov = animation.fov.remove(time);
+        position || rotation || fov
+    }
+
+    fn clear_keys(&mut self) {
+        self.animation = CameraAnimation { interpolation: self.animation.interpolation, ..Default::default() };
+    }
+
+    fn key_times(&self) -> Vec<f32> {
+        let animation = &self.animation;
+        merge_times(animation.position.times().chain(animation.rotation.times()).chain(animation.fov.times()))
+    }
+
+    fn interpolation_mut(&mut self) -> &mut Interpolation {
+        &mut self.animation.interpolation
+    }
+
+    fn sort_keys(&mut self) -> bool {
+        let animation = &mut self.animation;
+        animation.position.sort() && animation.rotation.sort() && animation.fov.sort()
+    }
+}
+
+fn merge_times(times: impl Iterator<Item = f32>) -> Vec<f32> {
+    let mut times: Vec<f32> = times.collect();
+    times.sort_by(f32::total_cmp);
+    times.dedup_by(|a, b| (*a - *b).abs() < KEY_TOLERANCE);
+    times
+}
+
+// Puts the whole scene where its keyframes say it is at `time`
+pub fn animate_scene(scene: &mut Scene, time: f32) {
+    scene.camera.animate(time);
+    for light in &mut scene.lights {
+        light.animate(time);
+    }
+    for mesh in &mut scene.objects {
+        mesh.animate(time);
+    }
+}
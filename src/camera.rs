@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+use crate::animation::CameraAnimation;
+use crate::math::*;
+
+// Degrees, how far the camera can look up or down when turned with `turn`
+pub const MAX_PITCH: f32 = 89.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+}
+
+// How the viewer moves the camera with the mouse and keyboard
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum CameraMode {
+    // Turns around a point in front of the camera, pans and zooms toward it
+    #[default]
+    Orbit,
+    // Looks around from where it is and flies with the keyboard
+    Fly,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Camera {
+    // Where the eye is in the world
+    pub position: [f32; 3],
+    // Degrees: pitch (x), yaw (y) and roll (z)
+    pub rotation: [f32; 3],
+    pub projection: Projection,
+    // Vertical field of view in degrees, perspective only
+    pub fov: f32,
+    // How many world units fit vertically on screen, orthographic only
+    pub ortho_height: f32,
+    pub near: f32,
+    pub far: f32,
+    #[serde(default)]
+    pub animation: CameraAnimation,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: [0.0, 0.0, 10.0],
+            rotation: [0.0, 0.0, 0.0],
+            projection: Projection::Perspective,
+            fov: 45.0,
+            ortho_height: 10.0,
+            near: 0.1,
+            far: 1000.0,
+            animation: CameraAnimation::default(),
+        }
+    }
+}
+
+impl Camera {
+    // World to camera space. Yaw is applied first, then pitch, then roll, so looking
+    // around never tilts the horizon.
+    pub fn view_matrix(&self) -> Mat4 {
+        let rotation = mat4_mul(
+            &mat4_rotation_z(self.rotation[2].to_radians()),
+            &mat4_mul(
+                &mat4_rotation_x(self.rotation[0].to_radians()),
+                &mat4_rotation_y(self.rotation[1].to_radians()),
+            ),
+        );
+        let translation = mat4_translation([-self.position[0], -self.position[1], -self.position[2]]);
+
+        mat4_mul(&rotation, &translation)
+    }
+
+    // The direction the camera is looking in, in world space
+    pub fn forward(&self) -> [f32; 3] {
+        // The view matrix maps this direction onto -Z, so it is the negated third row
+        let view = self.view_matrix();
+        [-view[2][0], -view[2][1], -view[2][2]]
+    }
+
+    // Screen right and screen up in world space
+    pub fn right(&self) -> [f32; 3] {
+        let view = self.view_matrix();
+        [view[0][0], view[0][1], view[0][2]]
+    }
+
+    pub fn up(&self) -> [f32; 3] {
+        let view = self.view_matrix();
+        [view[1][0], view[1][1], view[1][2]]
+    }
+
+    // Turns by degrees to the right and down. Pitch stops short of straight up or down,
+    // past that the view would flip over.
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.rotation[1] = (self.rotation[1] + yaw).rem_euclid(360.0);
+        self.rotation[0] = (self.rotation[0] + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // The point `distance` straight ahead, which orbiting turns around
+    pub fn target(&self, distance: f32) -> [f32; 3] {
+        vec3_add(self.position, vec3_scale(self.forward(), distance))
+    }
+
+    // Turns like `turn`, but moves so the target stays in the middle of the view
+    pub fn orbit(&mut self, distance: f32, yaw: f32, pitch: f32) {
+        let target = self.target(distance);
+        self.turn(yaw, pitch);
+        self.position = vec3_sub(target, vec3_scale(self.forward(), distance));
+    }
+
+    // Slides along the screen without turning
+    pub fn pan(&mut self, right: f32, up: f32) {
+        let offset = vec3_add(vec3_scale(self.right(), right), vec3_scale(self.up(), up));
+        self.position = vec3_add(self.position, offset);
+    }
+
+    // Moves toward the target until it is `distance * factor` away and returns that distance.
+    // Orthographic views look the same from any distance, so their view height shrinks along.
+    pub fn zoom(&mut self, distance: f32, factor: f32) -> f32 {
+        let target = self.target(distance);
+        let distance = (distance * factor).max(self.near * 2.0);
+        self.position = vec3_sub(target, vec3_scale(self.forward(), distance));
+        if self.projection == Projection::Orthographic {
+            self.ortho_height = (self.ortho_height * factor).max(0.1);
+        }
+        distance
+    }
+
+    // Aspect is width / height of whatever we are drawing into
+    pub fn projection_matrix(&self, aspect: f32) -> Mat4 {
+        match self.projection {
+            Projection::Perspective => mat4_perspective(self.fov.to_radians(), aspect, self.near, self.far),
+            Projection::Orthographic => {
+                let half_height = self.ortho_height / 2.0;
+                mat4_orthographic(half_height * aspect, half_height, self.near, self.far)
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// Sutherland-Hodgman clipping in homogeneous clip space.
+// Triangles that cross the edge of the view frustum get cut down to the part
+// that is inside instead of being thrown away.
+
+use crate::rasterizer::Varyings;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClipVertex {
+    pub position: [f32; 4],
+    pub varyings: Varyings,
+}
+
+impl ClipVertex {
+    pub fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        let mut position = [0.0; 4];
+        for (i, value) in position.iter_mut().enumerate() {
+            *value = self.position[i] + (other.position[i] - self.position[i]) * t;
+        }
+        ClipVertex {
+            position,
+            varyings: self.varyings.lerp(&other.varyings, t),
+        }
+    }
+}
+
+// Each plane is stored as the coefficients of a dot product with (x, y, z, w),
+// a vertex is inside when the result is >= 0.
+const FRUSTUM_PLANES: [[f32; 4]; 6] = [
+    [0.0, 0.0, 1.0, 1.0],  // near:   z >= -w
+    [0.0, 0.0, -1.0, 1.0], // far:    z <= w
+    [1.0, 0.0, 0.0, 1.0],  // left:   x >= -w
+    [-1.0, 0.0, 0.0, 1.0], // right:  x <= w
+    [0.0, 1.0, 0.0, 1.0],  // bottom: y >= -w
+    [0.0, -1.0, 0.0, 1.0], // top:    y <= w
+];
+
+fn plane_distance(plane: &[f32; 4], vertex: &ClipVertex) -> f32 {
+    let p = vertex.position;
+    plane[0] * p[0] + plane[1] * p[1] + plane[2] * p[2] + plane[3] * p[3]
+}
+
+// Clips a triangle against the view frustum and returns the remaining convex polygon.
+// The result is empty when nothing is visible, and can be drawn as a triangle fan.
+pub fn clip_triangle(triangle: [ClipVertex; 3]) -> Vec<ClipVertex> {
+    let mut fully_inside = true;
+    for plane in &FRUSTUM_PLANES {
+        let distances = triangle.map(|vertex| plane_distance(plane, &vertex));
+
+        // Everything is behind one plane, nothing to draw
+        if distances.iter().all(|distance| *distance < 0.0) {
+            return Vec::new();
+        }
+        if distances.iter().any(|distance| *distance < 0.0) {
+            fully_inside = false;
+        }
+    }
+
+    // Most triangles don't touch the frustum edges at all
+    if fully_inside {
+        return triangle.to_vec();
+    }
+
+    let mut polygon = triangle.to_vec();
+    for plane in &FRUSTUM_PLANES {
+        polygon = clip_against_plane(&polygon, plane);
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+
+    polygon
+}
+
+fn clip_against_plane(polygon: &[ClipVertex], plane: &[f32; 4]) -> Vec<ClipVertex> {
+    let mut output = Vec::with_capacity(polygon.len() + 1);
+
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        let current_distance = plane_distance(plane, current);
+        let next_distance = plane_distance(plane, next);
+
+        if current_distance >= 0.0 {
+            output.push(*current);
+        }
+
+        // The edge crosses the plane, keep the intersection point
+        if (current_distance >= 0.0) != (next_distance >= 0.0) {
+            let t = current_distance / (current_distance - next_distance);
+            output.push(current.lerp(next, t));
+        }
+    }
+
+    output
+}
@@ -0,0 +1,133 @@
+// Writes meshes back out to model files, the other direction of `import`.
+// With `bake_transforms` each mesh is moved to where it is in the world, parents included,
+// so the file looks like the scene does. Without it the vertices are written as they were loaded.
+
+use std::fmt::Write as _;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::math::*;
+use crate::scene::{Mesh, Scene};
+
+// Writes the objects at the given indices. The format comes from the file extension, .obj or .stl
+pub fn export_meshes(path: &Path, scene: &Scene, objects: &[usize], bake_transforms: bool) -> Result<(), Error> {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+
+    let world_matrices = scene.world_matrices();
+    let meshes: Vec<(&Mesh, Mat4)> = objects
+        .iter()
+        .filter_map(|index| {
+            let mesh = scene.objects.get(*index)?;
+            Some((mesh, if bake_transforms { world_matrices[*index] } else { mat4_identity() }))
+        })
+        .collect();
+
+    let bytes = match extension.as_deref() {
+        Some("obj") => write_obj(&meshes).into_bytes(),
+        Some("stl") => write_stl(&meshes),
+        _ => {
+            return Err(Error::new(ErrorKind::InvalidInput, "only .obj and .stl files can be exported"));
+        }
+    };
+
+    std::fs::write(path, bytes)
+}
+
+// Every mesh becomes its own object, with texture coordinates and normals when it has them.
+// Each mesh comes with the matrix that takes it to where it should be in the file.
+pub fn write_obj(meshes: &[(&Mesh, Mat4)]) -> String {
+    let mut output = String::from("# Exported from lad_engine_rust\n");
+
+    // OBJ indices count across the whole file, starting at 1
+    let mut offset = 1;
+
+    for (mesh, matrix) in meshes {
+        let _ = writeln!(output, "o {}", mesh.name);
+
+        for vertex in transform_vertices(mesh, matrix) {
+            let _ = writeln!(output, "v {} {} {}", vertex[0], vertex[1], vertex[2]);
+        }
+
+        let has_uvs = mesh.uvs.len() == mesh.vertices.len();
+        if has_uvs {
+            for uv in &mesh.uvs {
+                let _ = writeln!(output, "vt {} {}", uv.0, uv.1);
+            }
+        }
+
+        let has_normals = mesh.normals.len() == mesh.vertices.len();
+        if has_normals {
+            let normal_matrix = mat4_normal_matrix(matrix);
+            for normal in &mesh.normals {
+                let normal = vec3_normalize(mat4_transform_direction(&normal_matrix, [normal.0, normal.1, normal.2]));
+                let _ = writeln!(output, "vn {} {} {}", normal[0], normal[1], normal[2]);
+            }
+        }
+
+        // Positions, uvs and normals all line up, so every corner uses the same index for each
+        for triangle in mesh.indices.chunks_exact(3) {
+            output.push('f');
+            for index in corners(triangle, matrix) {
+                let index = index as usize + offset;
+                let _ = match (has_uvs, has_normals) {
+                    (true, true) => write!(output, " {}/{}/{}", index, index, index),
+                    (true, false) => write!(output, " {}/{}", index, index),
+                    (false, true) => write!(output, " {}//{}", index, index),
+                    (false, false) => write!(output, " {}", index),
+                };
+            }
+            output.push('\n');
+        }
+
+        offset += mesh.vertices.len();
+    }
+
+    output
+}
+
+// Binary STL, all meshes end up in one solid since STL has no way to tell them apart
+pub fn write_stl(meshes: &[(&Mesh, Mat4)]) -> Vec<u8> {
+    let triangle_count: usize = meshes.iter().map(|(mesh, _)| mesh.indices.len() / 3).sum();
+
+    let mut output = Vec::with_capacity(84 + triangle_count * 50);
+    // 80 byte header that must not start with "solid", or readers may take it for ASCII
+    output.extend_from_slice(b"Exported from lad_engine_rust");
+    output.resize(80, b' ');
+    output.extend_from_slice(&(triangle_count as u32).to_le_bytes());
+
+    for (mesh, matrix) in meshes {
+        let vertices = transform_vertices(mesh, matrix);
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = corners(triangle, matrix).map(|index| vertices[index as usize]);
+
+            let normal = calculate_normal(a, b, c);
+            // Degenerate triangles get a zero normal instead of NaN
+            let normal = if normal.iter().all(|value| value.is_finite()) { normal } else { [0.0; 3] };
+
+            for value in normal.iter().chain(&a).chain(&b).chain(&c) {
+                output.extend_from_slice(&value.to_le_bytes());
+            }
+            // Attribute byte count, nobody agrees on what to put here so it stays empty
+            output.extend_from_slice(&[0, 0]);
+        }
+    }
+
+    output
+}
+
+fn transform_vertices(mesh: &Mesh, matrix: &Mat4) -> Vec<[f32; 3]> {
+    mesh.vertices
+        .iter()
+        .map(|vertex| mat4_transform_point(matrix, [vertex.0, vertex.1, vertex.2]))
+        .collect()
+}
+
+// A mirroring matrix turns triangles inside out, swapping two corners turns them back
+fn corners(triangle: &[u32], matrix: &Mat4) -> [u32; 3] {
+    if mat4_determinant3(matrix) < 0.0 {
+        [triangle[0], triangle[2], triangle[1]]
+    } else {
+        [triangle[0], triangle[1], triangle[2]]
+    }
+}
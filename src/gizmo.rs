@@ -0,0 +1,220 @@
+// Handles for moving, turning and scaling an object with the mouse. This only does the math,
+// drawing the handles and reading the mouse is up to whatever shows the scene.
+// Every drag works from what the object looked like when it started, so nothing piles up
+// from one frame to the next.
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::*;
+use crate::picking::Ray;
+use crate::scene::Scene;
+use crate::transform::Transform;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum GizmoMode {
+    // Arrows along each axis
+    #[default]
+    Translate,
+    // A ring around each axis
+    Rotate,
+    // Boxes along each axis. Scaling is always along the object's own axes, those are
+    // the only ones a `Transform` can scale along.
+    Scale,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum GizmoSpace {
+    // The handles line up with the world axes
+    #[default]
+    World,
+    // The handles turn with the object
+    Local,
+}
+
+// Steps drags snap to when snapping is on
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Snapping {
+    pub enabled: bool,
+    // World units
+    pub translation: f32,
+    // Degrees
+    pub angle: f32,
+    pub scale: f32,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Snapping {
+            enabled: false,
+            translation: 0.5,
+            angle: 15.0,
+            scale: 0.1,
+        }
+    }
+}
+
+// Where the handles of an object go: its origin and the three directions they point in, in world space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GizmoFrame {
+    pub center: [f32; 3],
+    pub axes: [[f32; 3]; 3],
+}
+
+impl GizmoFrame {
+    pub fn of(scene: &Scene, object: usize, mode: GizmoMode, space: GizmoSpace) -> GizmoFrame {
+        let world = scene.world_matrix(object);
+        let center = mat4_transform_point(&world, [0.0, 0.0, 0.0]);
+        let world_axes = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+        let axes = if space == GizmoSpace::Local || mode == GizmoMode::Scale {
+            // An axis squashed flat by a zero scale has no direction left, the world one stands in
+            world_axes.map(|axis| {
+                let local = mat4_transform_direction(&world, axis);
+                if vec3_dot(local, local) > 1e-12 {
+                    vec3_normalize(local)
+                } else {
+                    axis
+                }
+            })
+        } else {
+            world_axes
+        };
+
+        GizmoFrame { center, axes }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GizmoDrag {
+    pub object: usize,
+    pub mode: GizmoMode,
+    pub axis: usize,
+    frame: GizmoFrame,
+    start: Transform,
+    // Takes world space into the space the object's transform is in
+    parent_inverse: Mat4,
+    // Where along the axis the drag was grabbed
+    grab: f32,
+    // For rings, the direction from the center to the mouse last frame
+    last_direction: [f32; 3],
+    // Radians turned so far, added up a frame at a time so it can go past half a turn
+    angle: f32,
+}
+
+impl GizmoDrag {
+    // None when the handle is looked at edge on and the mouse can't move it sensibly
+    pub fn start(scene: &Scene, object: usize, mode: GizmoMode, space: GizmoSpace, axis: usize, ray: &Ray) -> Option<GizmoDrag> {
+        let frame = GizmoFrame::of(scene, object, mode, space);
+        let parent_inverse = match scene.objects[object].parent {
+            Some(parent) => mat4_inverse(&scene.world_matrix(parent))?,
+            None => mat4_identity(),
+        };
+
+        let mut drag = GizmoDrag {
+            object,
+            mode,
+            axis,
+            frame,
+            start: scene.objects[object].transform,
+            parent_inverse,
+            grab: 0.0,
+            last_direction: [0.0; 3],
+            angle: 0.0,
+        };
+
+        match mode {
+            GizmoMode::Translate => drag.grab = drag.along_axis(ray)?,
+            GizmoMode::Rotate => drag.last_direction = drag.around_axis(ray)?,
+            GizmoMode::Scale => {
+                drag.grab = drag.along_axis(ray)?;
+                // Grabbed right at the center there is nothing to scale relative to
+                if drag.grab.abs() < 1e-4 {
+                    return None;
+                }
+            }
+        }
+        Some(drag)
+    }
+
+    // The transform the object has with the mouse along `ray`. Keeps the last one when the
+    // handle has turned edge on.
+    pub fn update(&mut self, ray: &Ray, snapping: Snapping) -> Option<Transform> {
+        let axis = self.frame.axes[self.axis];
+        let mut transform = self.start;
+
+        match self.mode {
+            GizmoMode::Translate => {
+                let mut offset = self.along_axis(ray)? - self.grab;
+                if snapping.enabled {
+                    offset = snap(offset, snapping.translation);
+                }
+                let position = vec3_add(self.frame.center, vec3_scale(axis, offset));
+                transform.translation = mat4_transform_point(&self.parent_inverse, position);
+            }
+            GizmoMode::Rotate => {
+                let direction = self.around_axis(ray)?;
+                let turned = vec3_dot(vec3_cross(self.last_direction, direction), axis);
+                self.angle += turned.atan2(vec3_dot(self.last_direction, direction));
+                self.last_direction = direction;
+
+                let mut angle = self.angle;
+                if snapping.enabled {
+                    angle = snap(angle.to_degrees(), snapping.angle).to_radians();
+                }
+                // Turning around a world axis is turning around that axis as the parent sees it
+                let parent_axis = mat4_transform_direction(&self.parent_inverse, axis);
+                transform.rotation = quat_normalize(quat_mul(quat_from_axis_angle(parent_axis, angle), self.start.rotation));
+            }
+            GizmoMode::Scale => {
+                // Dragging through the center would turn the object inside out, it stops just short
+                let factor = (self.along_axis(ray)? / self.grab).max(MIN_SCALE_FACTOR);
+                let mut scale = self.start.scale[self.axis] * factor;
+                if snapping.enabled && snapping.scale > 0.0 {
+                    scale = scale.signum() * snap(scale.abs(), snapping.scale).max(snapping.scale);
+                }
+                transform.scale[self.axis] = scale;
+            }
+        }
+
+        Some(transform)
+    }
+
+    // How far along the axis line the point closest to the ray is
+    fn along_axis(&self, ray: &Ray) -> Option<f32> {
+        let axis = self.frame.axes[self.axis];
+        let to_center = vec3_sub(self.frame.center, ray.origin);
+        let b = vec3_dot(axis, ray.direction);
+        let denominator = 1.0 - b * b;
+        // Pointing straight at the camera
+        if denominator < 1e-6 {
+            return None;
+        }
+        Some((b * vec3_dot(ray.direction, to_center) - vec3_dot(axis, to_center)) / denominator)
+    }
+
+    // Direction from the center to where the ray crosses the plane of the ring
+    fn around_axis(&self, ray: &Ray) -> Option<[f32; 3]> {
+        let axis = self.frame.axes[self.axis];
+        let facing = vec3_dot(ray.direction, axis);
+        if facing.abs() < 1e-4 {
+            return None;
+        }
+        let distance = vec3_dot(vec3_sub(self.frame.center, ray.origin), axis) / facing;
+        let offset = vec3_sub(ray.at(distance), self.frame.center);
+        if vec3_dot(offset, offset) < 1e-12 {
+            return None;
+        }
+        Some(vec3_normalize(offset))
+    }
+}
+
+// Smallest part of its size a scale drag can shrink an axis to
+const MIN_SCALE_FACTOR: f32 = 0.01;
+
+fn snap(value: f32, step: f32) -> f32 {
+    if step <= 0.0 {
+        return value;
+    }
+    (value / step).round() * step
+}
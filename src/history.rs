@@ -0,0 +1,337 @@
+// Undo and redo for scene edits. Every edit is stored as the command that takes it back,
+// and carrying out a command gives back the one that redoes it, so both stacks hold the same kind of thing.
+
+use std::sync::Arc;
+
+use crate::animation::ObjectAnimation;
+use crate::camera::Camera;
+use crate::material::Material;
+use crate::scene::{CullMode, Light, Mesh, Scene};
+use crate::transform::Transform;
+
+// Older steps get forgotten past this many
+pub const MAX_HISTORY: usize = 200;
+
+// Everything about an object the settings and the timeline can change, the geometry never does
+#[derive(Clone, Debug)]
+pub struct ObjectState {
+    pub name: String,
+    pub transform: Transform,
+    pub parent: Option<usize>,
+    pub cull_mode: CullMode,
+    pub material: Material,
+    pub animation: ObjectAnimation,
+}
+
+impl ObjectState {
+    pub fn of(mesh: &Mesh) -> Self {
+        ObjectState {
+            name: mesh.name.clone(),
+            transform: mesh.transform,
+            parent: mesh.parent,
+            cull_mode: mesh.cull_mode,
+            material: mesh.material.clone(),
+            animation: mesh.animation.clone(),
+        }
+    }
+
+    fn apply_to(self, mesh: &mut Mesh) {
+        mesh.name = self.name;
+        mesh.transform = self.transform;
+        mesh.parent = self.parent;
+        mesh.cull_mode = self.cull_mode;
+        mesh.material = self.material;
+        mesh.animation = self.animation;
+    }
+}
+
+impl PartialEq for ObjectState {
+    fn eq(&self, other: &Self) -> bool {
+        // Textures are compared by identity, going over every texel each frame would be slow
+        let same_texture = match (&self.material.texture, &other.material.texture) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        let untextured = |state: &ObjectState| Material { texture: None, ..state.material.clone() };
+
+        same_texture
+            && self.name == other.name
+            && self.transform == other.transform
+            && self.parent == other.parent
+            && self.cull_mode == other.cull_mode
+            && self.animation == other.animation
+            && untextured(self) == untextured(other)
+    }
+}
+
+#[derive(Debug)]
+pub enum Command {
+    SetObject { index: usize, state: ObjectState },
+    SetLight { index: usize, light: Light },
+    SetCamera(Camera),
+    // The meshes end up at `index` onwards in the order given
+    InsertObjects { index: usize, meshes: Vec<Mesh> },
+    RemoveObjects { index: usize, count: usize },
+    InsertLight { index: usize, light: Light },
+    RemoveLight { index: usize },
+    // Several commands that are undone and redone together
+    Batch(Vec<Command>),
+}
+
+impl Command {
+    // Carries the command out and returns the one that takes it back.
+    // Commands pointing past the end of the scene do nothing and come back unchanged.
+    pub fn apply(self, scene: &mut Scene) -> Command {
+        match self {
+            Command::SetObject { index, state } => match scene.objects.get_mut(index) {
+                Some(mesh) => {
+                    let previous = ObjectState::of(mesh);
+                    state.apply_to(mesh);
+                    Command::SetObject { index, state: previous }
+                }
+                None => Command::SetObject { index, state },
+            },
+            Command::SetLight { index, light } => match scene.lights.get_mut(index) {
+                Some(current) => Command::SetLight { index, light: std::mem::replace(current, light) },
+                None => Command::SetLight { index, light },
+            },
+            Command::SetCamera(camera) => Command::SetCamera(std::mem::replace(&mut scene.camera, camera)),
+            // One at a time, backwards, so the parent indices come out as they were before the removal
+            Command::InsertObjects { index, meshes } => {
+                let index = index.min(scene.objects.len());
+                let count = meshes.len();
+                for mesh in meshes.into_iter().rev() {
+                    scene.insert_object(index, mesh);
+                }
+                Command::RemoveObjects { index, count }
+            }
+            Command::RemoveObjects { index, count } => {
+                let count = count.min(scene.objects.len().saturating_sub(index));
+                let meshes = (0..count).map(|_| scene.remove_object(index)).collect();
+                Command::InsertObjects { index, meshes }
+            }
+            Command::InsertLight { index, light } => {
+                let index = index.min(scene.lights.len());
+                scene.lights.insert(index, light);
+                Command::RemoveLight { index }
+            }
+            Command::RemoveLight { index } => {
+                if index < scene.lights.len() {
+                    Command::InsertLight { index, light: scene.lights.remove(index) }
+                } else {
+                    Command::RemoveLight { index }
+                }
+            }
+            Command::Batch(commands) => {
+                let mut undo: Vec<Command> = commands.into_iter().map(|command| command.apply(scene)).collect();
+                undo.reverse();
+                Command::Batch(undo)
+            }
+        }
+    }
+
+    // Where objects end up once the command is carried out, worked out before carrying it out
+    pub fn object_moves(&self) -> ObjectMoves {
+        let mut moves = ObjectMoves::default();
+        self.collect_moves(&mut moves.0);
+        moves
+    }
+
+    fn collect_moves(&self, moves: &mut Vec<ObjectMove>) {
+        match self {
+            Command::InsertObjects { index, meshes } => moves.push(ObjectMove::Inserted { index: *index, count: meshes.len() }),
+            Command::RemoveObjects { index, count } => moves.push(ObjectMove::Removed { index: *index, count: *count }),
+            Command::Batch(commands) => commands.iter().for_each(|command| command.collect_moves(moves)),
+            _ => {}
+        }
+    }
+
+    // Whether both change the same thing, so one can stand in for the other when merging
+    fn same_target(&self, other: &Command) -> bool {
+        match (self, other) {
+            (Command::SetObject { index: a, .. }, Command::SetObject { index: b, .. }) => a == b,
+            (Command::SetLight { index: a, .. }, Command::SetLight { index: b, .. }) => a == b,
+            (Command::SetCamera(_), Command::SetCamera(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ObjectMove {
+    Inserted { index: usize, count: usize },
+    Removed { index: usize, count: usize },
+}
+
+// The objects a step added and removed, in order, so indices kept outside the scene
+// (the selection, the spinning object) can follow the objects they point at
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectMoves(Vec<ObjectMove>);
+
+impl ObjectMoves {
+    // Where the object that was at `index` is now, None when it was removed
+    pub fn follow(&self, index: usize) -> Option<usize> {
+        self.0.iter().try_fold(index, |index, step| match *step {
+            ObjectMove::Inserted { index: at, count } if index >= at => Some(index + count),
+            ObjectMove::Removed { index: at, count } if index >= at + count => Some(index - count),
+            ObjectMove::Removed { index: at, .. } if index >= at => None,
+            _ => Some(index),
+        })
+    }
+
+    // The first object the step added that is still there at the end
+    pub fn first_inserted(&self) -> Option<usize> {
+        self.0.iter().enumerate().find_map(|(position, step)| match *step {
+            ObjectMove::Inserted { index, count } if count > 0 => {
+                let later = ObjectMoves(self.0[position + 1..].to_vec());
+                later.follow(index)
+            }
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    // `undo` is the command that takes back an edit that was just made
+    pub fn record(&mut self, undo: Command) {
+        self.redo.clear();
+        self.undo.push(undo);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+    }
+
+    // Like `record`, but an edit continuing the last one (say, the next frame of a drag)
+    // is left out, the step already on the stack goes back to before both of them
+    pub fn record_merged(&mut self, undo: Command) {
+        if self.undo.last().is_some_and(|last| last.same_target(&undo)) {
+            self.redo.clear();
+            return;
+        }
+        self.record(undo);
+    }
+
+    // Both return None when there was nothing to do, otherwise how the objects moved
+    pub fn undo(&mut self, scene: &mut Scene) -> Option<ObjectMoves> {
+        let command = self.undo.pop()?;
+        let moves = command.object_moves();
+        self.redo.push(command.apply(scene));
+        Some(moves)
+    }
+
+    pub fn redo(&mut self, scene: &mut Scene) -> Option<ObjectMoves> {
+        let command = self.redo.pop()?;
+        let moves = command.object_moves();
+        self.undo.push(command.apply(scene));
+        Some(moves)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(name: &str, parent: Option<usize>) -> Mesh {
+        serde_json::from_value(serde_json::json!({ "name": name, "vertices": [], "indices": [], "parent": parent })).unwrap()
+    }
+
+    fn outline(scene: &Scene) -> Vec<(String, Option<usize>)> {
+        scene.objects.iter().map(|mesh| (mesh.name.clone(), mesh.parent)).collect()
+    }
+
+    fn expected(pairs: &[(&str, Option<usize>)]) -> Vec<(String, Option<usize>)> {
+        pairs.iter().map(|(name, parent)| (name.to_string(), *parent)).collect()
+    }
+
+    // The way the viewer deletes: children move up to the deleted object's parent first
+    fn delete_with_children(scene: &mut Scene, history: &mut History) -> ObjectMoves {
+        let mut child = ObjectState::of(&scene.objects[2]);
+        child.parent = Some(0);
+        let command = Command::Batch(vec![
+            Command::SetObject { index: 2, state: child },
+            Command::RemoveObjects { index: 1, count: 1 },
+        ]);
+        let moves = command.object_moves();
+        history.record(command.apply(scene));
+        moves
+    }
+
+    fn scene() -> Scene {
+        Scene {
+            camera: Camera::default(),
+            objects: vec![
+                mesh("root", None),
+                mesh("deleted", Some(0)),
+                mesh("child", Some(1)),
+                mesh("grandchild", Some(2)),
+                mesh("other", None),
+            ],
+            lights: vec![],
+            animation_length: 1.0,
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_a_delete_with_children() {
+        let mut scene = scene();
+        let mut history = History::new();
+        let before = outline(&scene);
+        let after = expected(&[("root", None), ("child", Some(0)), ("grandchild", Some(1)), ("other", None)]);
+
+        let moves = delete_with_children(&mut scene, &mut history);
+        assert_eq!(outline(&scene), after);
+        assert_eq!(moves.follow(1), None);
+        assert_eq!(moves.follow(4), Some(3));
+        assert_eq!(moves.follow(0), Some(0));
+
+        let moves = history.undo(&mut scene).unwrap();
+        assert_eq!(outline(&scene), before);
+        assert_eq!(moves.first_inserted(), Some(1));
+        assert_eq!(moves.follow(3), Some(4));
+        assert!(!history.can_undo());
+
+        let moves = history.redo(&mut scene).unwrap();
+        assert_eq!(outline(&scene), after);
+        assert_eq!(moves.follow(1), None);
+        assert_eq!(moves.first_inserted(), None);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo() {
+        let mut scene = scene();
+        let mut history = History::new();
+
+        delete_with_children(&mut scene, &mut history);
+        history.undo(&mut scene);
+        assert!(history.can_redo());
+
+        history.record(Command::SetCamera(Camera::default()));
+        assert!(!history.can_redo());
+        assert!(history.redo(&mut scene).is_none());
+    }
+}
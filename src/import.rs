@@ -0,0 +1,253 @@
+// Turns model files into meshes. Everything goes through `import_mesh`, whether the file
+// comes from a path, a drag and drop in the browser or is baked into the binary.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::animation::ObjectAnimation;
+use crate::normals::{generate_normals, DEFAULT_CREASE_ANGLE};
+use crate::material::Material;
+use crate::scene::{CullMode, Mesh};
+use crate::transform::Transform;
+use crate::triangulate::triangulate;
+
+mod gltf;
+mod mtl;
+mod obj;
+mod ply;
+mod stl;
+
+#[derive(Debug)]
+pub enum ImportError {
+    // The file (or a file it needs) couldn't be read
+    Io(std::io::Error),
+    // The file is broken, lines count from 1
+    Parse { line: usize, message: String },
+    // The file is broken somewhere that has no line to point at, like in binary data
+    Invalid(String),
+    // The file is fine but uses something we can't handle
+    Unsupported(String),
+    // A face points at a vertex, texture coordinate or normal that doesn't exist
+    IndexOutOfRange { line: usize, index: i64, count: usize },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "couldn't read the file: {}", err),
+            ImportError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ImportError::Invalid(message) => write!(f, "{}", message),
+            ImportError::Unsupported(feature) => write!(f, "not supported: {}", feature),
+            ImportError::IndexOutOfRange { line, index, count } => {
+                write!(f, "line {}: index {} is out of range, there are only {}", line, index, count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(err: std::io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+// Where a model comes from
+pub enum ImportSource<'a> {
+    // Native builds and the command line, files next to it (materials, textures) can be found
+    Path(&'a Path),
+    // Dropped in the browser or baked in with include_bytes!, nothing else can be loaded.
+    // The name is the file name and decides the format.
+    Bytes { name: &'a str, bytes: &'a [u8] },
+}
+
+// Loads every mesh in a model file
+pub fn import_mesh(source: ImportSource) -> Result<Vec<Mesh>, ImportError> {
+    import_mesh_with_warnings(source, &mut vec![])
+}
+
+// Like `import_mesh`, but also hands back the problems that didn't stop the import,
+// like a missing texture or a statement that got skipped
+pub fn import_mesh_with_warnings(source: ImportSource, warnings: &mut Vec<String>) -> Result<Vec<Mesh>, ImportError> {
+    let (file_name, bytes, directory) = match source {
+        ImportSource::Path(path) => {
+            let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            (file_name, std::fs::read(path)?, path.parent())
+        }
+        ImportSource::Bytes { name, bytes } => (name.to_string(), bytes.to_vec(), None),
+    };
+
+    let file_path = Path::new(&file_name);
+    let stem = file_path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+    let extension = file_path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+
+    let name = stem.as_deref().unwrap_or("Imported Object");
+
+    // Binary glTF and PLY say what they are in their first bytes, whatever the file is called
+    if bytes.starts_with(b"glTF") {
+        return gltf::read(&bytes, stem.as_deref(), directory, warnings);
+    }
+    if bytes.starts_with(b"ply\n") || bytes.starts_with(b"ply\r\n") {
+        return Ok(vec![ply::read(&bytes, name)?]);
+    }
+
+    match extension.as_deref() {
+        Some("obj") => read_obj(&bytes, stem.as_deref(), directory, warnings),
+        Some("gltf") | Some("glb") => gltf::read(&bytes, stem.as_deref(), directory, warnings),
+        Some("stl") => Ok(vec![stl::read(&bytes, name)?]),
+        Some("ply") => Ok(vec![ply::read(&bytes, name)?]),
+        Some(extension) => Err(ImportError::Unsupported(format!(".{} files", extension))),
+        None => Err(ImportError::Unsupported(format!("'{}' has no file extension", file_name))),
+    }
+}
+
+fn empty_mesh(name: &str, position: [f32; 3]) -> Mesh {
+    Mesh {
+        name: name.to_string(),
+        vertices: vec![],
+        normals: vec![],
+        uvs: vec![],
+        colors: vec![],
+        indices: vec![],
+        face_count: 0,
+        transform: Transform::from_translation(position),
+        parent: None,
+        cull_mode: CullMode::default(),
+        material: Material::default(),
+        animation: ObjectAnimation::default(),
+    }
+}
+
+// Turns an obj's faces into our vertex and index buffers, one mesh per material.
+// Faces without a material that could be found all end up in a single mesh with the default one.
+fn read_obj(bytes: &[u8], file_stem: Option<&str>, directory: Option<&Path>, warnings: &mut Vec<String>) -> Result<Vec<Mesh>, ImportError> {
+    let data = obj::parse(&String::from_utf8_lossy(bytes), warnings)?;
+
+    let name: &str = data.name.as_deref().or(file_stem).unwrap_or("Imported Object");
+
+    let materials = match directory {
+        Some(directory) => load_materials(&data.material_libraries, directory, warnings),
+        None => {
+            if !data.material_libraries.is_empty() {
+                warnings.push("materials can only be loaded for files opened from a path".to_string());
+            }
+            HashMap::new()
+        }
+    };
+
+    // Faces only get split off when their material was actually found
+    let face_material = |face: &obj::Face| -> Option<usize> {
+        face.material.filter(|&index| materials.contains_key(&data.materials[index]))
+    };
+
+    // Keep the meshes in the order their materials first show up in the file
+    let mut order: Vec<Option<usize>> = vec![];
+    for face in &data.faces {
+        let material = face_material(face);
+        if !order.contains(&material) {
+            order.push(material);
+        }
+    }
+    if order.is_empty() {
+        order.push(None);
+    }
+
+    let mut meshes = vec![];
+    for material in &order {
+        let faces = data.faces.iter().filter(|face| face_material(face) == *material);
+
+        let mut mesh = build_mesh(&data, faces);
+        let material_name = material.map(|index| data.materials[index].as_str());
+        mesh.name = match material_name {
+            Some(material_name) if order.len() > 1 => format!("{} ({})", name, material_name),
+            _ => name.to_string(),
+        };
+        if let Some(material) = material_name.and_then(|material_name| materials.get(material_name)) {
+            mesh.material = material.clone();
+        }
+        meshes.push(mesh);
+    }
+
+    Ok(meshes)
+}
+
+// Corners that share a position, texture coordinate and normal become one vertex.
+fn build_mesh<'a>(data: &obj::ObjData, faces: impl Iterator<Item = &'a obj::Face>) -> Mesh {
+    let mut mesh = empty_mesh("", [0.0, 0.0, 0.0]);
+
+    let mut lookup: HashMap<obj::Corner, u32> = HashMap::new();
+    // The normals the file gives, per vertex
+    let mut authored_normals = vec![];
+    let mut has_uvs = false;
+
+    for face in faces {
+        let mut polygon_indices = Vec::with_capacity(face.corners.len());
+        for corner in &face.corners {
+            let index = *lookup.entry(*corner).or_insert_with(|| {
+                let position = data.positions[corner.position];
+                let uv = match corner.tex_coord {
+                    Some(t) => {
+                        has_uvs = true;
+                        (data.tex_coords[t][0], data.tex_coords[t][1])
+                    }
+                    None => (0.0, 0.0),
+                };
+                let normal = corner.normal.map(|n| (data.normals[n][0], data.normals[n][1], data.normals[n][2]));
+
+                mesh.vertices.push((position[0], position[1], position[2]));
+                authored_normals.push(normal);
+                mesh.uvs.push(uv);
+                (mesh.vertices.len() - 1) as u32
+            });
+            polygon_indices.push(index);
+        }
+
+        // Quads and bigger polygons get cut into triangles
+        let points: Vec<[f32; 3]> = face.corners.iter().map(|corner| data.positions[corner.position]).collect();
+        for triangle in triangulate(&points) {
+            mesh.indices.extend(triangle.map(|corner| polygon_indices[corner]));
+        }
+        mesh.face_count += 1;
+    }
+
+    if !has_uvs {
+        mesh.uvs.clear();
+    }
+
+    // Smooth shading needs a normal on every vertex. Corners without one get them worked out
+    // from the faces around them, the ones the file gives are kept.
+    if authored_normals.iter().any(Option::is_none) {
+        generate_normals(&mut mesh, DEFAULT_CREASE_ANGLE);
+        for (normal, authored) in mesh.normals.iter_mut().zip(authored_normals) {
+            if let Some(authored) = authored {
+                *normal = authored;
+            }
+        }
+    } else {
+        mesh.normals = authored_normals.into_iter().flatten().collect();
+    }
+
+    mesh
+}
+
+// Reads every material library the obj asks for. One that can't be read only costs
+// its materials, so it's a warning rather than an error.
+fn load_materials(libraries: &[String], directory: &Path, warnings: &mut Vec<String>) -> HashMap<String, Material> {
+    let mut materials = HashMap::new();
+
+    for library in libraries {
+        let path = directory.join(library);
+        let result = std::fs::read(&path)
+            .map_err(ImportError::from)
+            .and_then(|bytes| mtl::parse(&String::from_utf8_lossy(&bytes), directory, warnings));
+
+        match result {
+            Ok(library) => materials.extend(library),
+            Err(err) => warnings.push(format!("material library {}: {}", path.display(), err)),
+        }
+    }
+
+    materials
+}
@@ -0,0 +1,250 @@
+// glTF 2.0 importer for both .gltf and .glb files.
+// Every primitive becomes its own mesh, placed, turned and scaled the way its node ends up in the default scene.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use gltf::Gltf;
+
+use super::{empty_mesh, ImportError};
+use crate::material::Material;
+use crate::math::{mat4_identity, mat4_mul, Mat4};
+use crate::normals::{generate_normals, DEFAULT_CREASE_ANGLE};
+use crate::scene::{CullMode, Mesh};
+use crate::texture::Texture;
+use crate::transform::Transform;
+
+// External buffers and images are looked up relative to `directory`, without one they have to be embedded
+pub fn read(bytes: &[u8], file_stem: Option<&str>, directory: Option<&Path>, warnings: &mut Vec<String>) -> Result<Vec<Mesh>, ImportError> {
+    let gltf = Gltf::from_slice(bytes).map_err(convert_error)?;
+    let document = &gltf.document;
+    let buffers = gltf::import_buffers(document, directory, gltf.blob.clone()).map_err(convert_error)?;
+
+    let textures = match gltf::import_images(document, directory, &buffers) {
+        Ok(images) => images.into_iter().map(|image| convert_image(image, warnings)).collect(),
+        Err(err) => {
+            warnings.push(format!("textures couldn't be loaded: {}", err));
+            vec![]
+        }
+    };
+
+    let materials: Vec<Material> = document.materials().map(|material| convert_material(&material, &textures)).collect();
+
+    let Some(scene) = document.default_scene().or_else(|| document.scenes().next()) else {
+        return Err(ImportError::Invalid("the file has no scenes".to_string()));
+    };
+
+    if document.animations().next().is_some() || document.skins().next().is_some() {
+        warnings.push("animations and skins are not supported, only the rest pose is imported".to_string());
+    }
+
+    let mut meshes = vec![];
+    // Nodes go on the stack backwards so they come off in the order of the file
+    let mut stack: Vec<(gltf::Node, Mat4)> = scene.nodes().map(|node| (node, mat4_identity())).collect();
+    stack.reverse();
+
+    while let Some((node, parent)) = stack.pop() {
+        let world = mat4_mul(&parent, &node_matrix(&node));
+
+        if let Some(mesh) = node.mesh() {
+            let name = node.name().or(mesh.name()).or(file_stem).unwrap_or("Imported Object");
+            let primitive_count = mesh.primitives().len();
+
+            for (index, primitive) in mesh.primitives().enumerate() {
+                let Some(mut output) = read_primitive(&primitive, &buffers, &world, warnings) else {
+                    continue;
+                };
+
+                output.name = if primitive_count > 1 {
+                    format!("{} ({})", name, primitive.material().name().map(str::to_string).unwrap_or(index.to_string()))
+                } else {
+                    name.to_string()
+                };
+
+                let material = &primitive.material();
+                if let Some(material) = material.index().and_then(|index| materials.get(index)) {
+                    output.material = material.clone();
+                }
+                // glTF fronts are counter-clockwise, and single sided materials ask for their backs to be culled
+                if !material.double_sided() {
+                    output.cull_mode = CullMode::Clockwise;
+                }
+
+                meshes.push(output);
+            }
+        }
+
+        let children: Vec<gltf::Node> = node.children().collect();
+        stack.extend(children.into_iter().rev().map(|child| (child, world)));
+    }
+
+    if meshes.is_empty() {
+        warnings.push("the file has no triangle meshes".to_string());
+        meshes.push(empty_mesh(file_stem.unwrap_or("Imported Object"), [0.0, 0.0, 0.0]));
+    }
+
+    Ok(meshes)
+}
+
+// glTF stores matrices column by column, ours are row by row
+fn node_matrix(node: &gltf::Node) -> Mat4 {
+    let columns = node.transform().matrix();
+    let mut matrix = [[0.0; 4]; 4];
+    for (row, matrix_row) in matrix.iter_mut().enumerate() {
+        for (column, value) in matrix_row.iter_mut().enumerate() {
+            *value = columns[column][row];
+        }
+    }
+    matrix
+}
+
+fn read_primitive(primitive: &gltf::Primitive, buffers: &[gltf::buffer::Data], world: &Mat4, warnings: &mut Vec<String>) -> Option<Mesh> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let Some(positions) = reader.read_positions() else {
+        warnings.push("skipped a primitive without positions".to_string());
+        return None;
+    };
+    let positions: Vec<[f32; 3]> = positions.collect();
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    // Strips and fans are just a different way of writing down triangles
+    let mut triangles: Vec<u32> = match primitive.mode() {
+        Mode::Triangles => indices,
+        Mode::TriangleStrip => (2..indices.len())
+            .flat_map(|i| {
+                if i % 2 == 0 {
+                    [indices[i - 2], indices[i - 1], indices[i]]
+                } else {
+                    [indices[i - 1], indices[i - 2], indices[i]]
+                }
+            })
+            .collect(),
+        Mode::TriangleFan => (2..indices.len()).flat_map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+        mode => {
+            warnings.push(format!("skipped a primitive drawn as {:?}, only triangles are supported", mode));
+            return None;
+        }
+    };
+    triangles.truncate(triangles.len() / 3 * 3);
+
+    if let Some(index) = triangles.iter().find(|index| **index as usize >= positions.len()) {
+        warnings.push(format!("skipped a primitive with index {} out of range, there are only {}", index, positions.len()));
+        return None;
+    }
+
+    let mut mesh = empty_mesh("", [0.0, 0.0, 0.0]);
+    mesh.transform = Transform::from_matrix(world);
+    mesh.vertices = positions.iter().map(|vertex| (vertex[0], vertex[1], vertex[2])).collect();
+
+    if let Some(normals) = reader.read_normals() {
+        mesh.normals = normals.map(|normal| (normal[0], normal[1], normal[2])).collect();
+    }
+
+    // glTF puts the texture origin in the top left, ours is in the bottom left like OBJ
+    if let Some(tex_coords) = reader.read_tex_coords(0) {
+        mesh.uvs = tex_coords.into_f32().map(|uv| (uv[0], 1.0 - uv[1])).collect();
+    }
+
+    if let Some(colors) = reader.read_colors(0) {
+        mesh.colors = colors.into_rgb_f32().collect();
+    }
+
+    mesh.face_count = triangles.len() / 3;
+    mesh.indices = triangles;
+
+    if mesh.normals.len() != mesh.vertices.len() {
+        mesh.normals.clear();
+        generate_normals(&mut mesh, DEFAULT_CREASE_ANGLE);
+    }
+
+    Some(mesh)
+}
+
+// Only the base color, emission and roughly how shiny it is. Metallic surfaces get highlights
+// in their own color.
+fn convert_material(material: &gltf::Material, textures: &[Option<Arc<Texture>>]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [red, green, blue, alpha] = pbr.base_color_factor();
+    let metallic = pbr.metallic_factor();
+    let roughness = pbr.roughness_factor().clamp(0.0, 1.0);
+
+    let mut output = Material {
+        diffuse: [red, green, blue],
+        emissive: material.emissive_factor(),
+        // Blinn-Phong exponent that gives about the same highlight size as the roughness
+        shininess: (2.0 / roughness.powi(4).max(1e-4) - 2.0).clamp(1.0, 512.0),
+        specular: [
+            1.0 + (red - 1.0) * metallic,
+            1.0 + (green - 1.0) * metallic,
+            1.0 + (blue - 1.0) * metallic,
+        ],
+        ..Material::default()
+    };
+
+    if material.alpha_mode() == gltf::material::AlphaMode::Blend {
+        output.opacity = alpha;
+    }
+
+    if let Some(info) = pbr.base_color_texture() {
+        output.texture = textures.get(info.texture().source().index()).cloned().flatten();
+    }
+
+    output
+}
+
+fn convert_image(image: gltf::image::Data, warnings: &mut Vec<String>) -> Option<Arc<Texture>> {
+    let channels = match image.format {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        format => {
+            warnings.push(format!("{:?} textures are not supported", format));
+            return None;
+        }
+    };
+
+    // Sampling wraps around the size, which has nothing to wrap around when it is 0
+    if image.width == 0 || image.height == 0 {
+        warnings.push("skipped an empty texture".to_string());
+        return None;
+    }
+
+    // Grayscale images use their one color channel for all three
+    let texels: Vec<[f32; 3]> = image
+        .pixels
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let color = if channels < 3 { [pixel[0]; 3] } else { [pixel[0], pixel[1], pixel[2]] };
+            color.map(|channel| channel as f32 / 255.0)
+        })
+        .collect();
+    if texels.len() != image.width as usize * image.height as usize {
+        warnings.push(format!("skipped a {}x{} texture with the wrong amount of pixel data", image.width, image.height));
+        return None;
+    }
+
+    Some(Arc::new(Texture {
+        width: image.width as usize,
+        height: image.height as usize,
+        texels,
+    }))
+}
+
+fn convert_error(err: gltf::Error) -> ImportError {
+    match err {
+        gltf::Error::Io(err) => ImportError::Io(err),
+        gltf::Error::Deserialize(err) => ImportError::Parse {
+            line: err.line(),
+            message: err.to_string(),
+        },
+        err => ImportError::Invalid(err.to_string()),
+    }
+}
@@ -0,0 +1,119 @@
+// Wavefront MTL parser, the material libraries OBJ files point at with `mtllib`
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::ImportError;
+use crate::material::Material;
+use crate::texture::Texture;
+
+// Textures are looked up relative to `directory`. Statements we have no use for are skipped.
+pub fn parse(text: &str, directory: &Path, warnings: &mut Vec<String>) -> Result<HashMap<String, Material>, ImportError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+
+    for (number, line) in text.lines().enumerate() {
+        let line_number = number + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut parts = line.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        let args: Vec<&str> = parts.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((args.join(" "), Material::default()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            return Err(ImportError::Parse {
+                line: line_number,
+                message: format!("'{}' before any newmtl", keyword),
+            });
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_color(&args, line_number)?,
+            "Ka" => material.ambient = parse_color(&args, line_number)?,
+            "Ks" => {
+                // Ks is the whole highlight color already
+                material.specular = parse_color(&args, line_number)?;
+                material.specular_strength = 1.0;
+            }
+            "Ke" => material.emissive = parse_color(&args, line_number)?,
+            "Ns" => material.shininess = parse_number(&args, line_number)?.max(1.0),
+            "d" => material.opacity = parse_number(&args, line_number)?.clamp(0.0, 1.0),
+            "Tr" => material.opacity = (1.0 - parse_number(&args, line_number)?).clamp(0.0, 1.0),
+            "map_Kd" => {
+                // Options like `-s 1 1 1` come first, the file name is last
+                let Some(file) = args.last() else {
+                    return Err(ImportError::Parse {
+                        line: line_number,
+                        message: "map_Kd needs a file name".to_string(),
+                    });
+                };
+
+                let path = directory.join(file);
+                let texture = std::fs::read(&path)
+                    .map_err(|err| format!("Error opening texture {}: {}", path.display(), err))
+                    .and_then(|bytes| Texture::from_bytes(&bytes));
+
+                match texture {
+                    Ok(texture) => material.texture = Some(Arc::new(texture)),
+                    Err(err) => warnings.push(err),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+
+    Ok(materials)
+}
+
+fn parse_number(args: &[&str], line: usize) -> Result<f32, ImportError> {
+    match args {
+        [value] => value.parse().map_err(|_| ImportError::Parse {
+            line,
+            message: format!("'{}' is not a number", value),
+        }),
+        _ => Err(ImportError::Parse {
+            line,
+            message: format!("expected 1 number, found {}", args.len()),
+        }),
+    }
+}
+
+// Only plain RGB colors are supported, `Kd 0.5` is short for `Kd 0.5 0.5 0.5`
+fn parse_color(args: &[&str], line: usize) -> Result<[f32; 3], ImportError> {
+    if matches!(args.first(), Some(&"xyz") | Some(&"spectral")) {
+        return Err(ImportError::Unsupported(format!("CIE XYZ and spectral colors (line {})", line)));
+    }
+
+    let values = args
+        .iter()
+        .map(|arg| {
+            arg.parse::<f32>().map_err(|_| ImportError::Parse {
+                line,
+                message: format!("'{}' is not a number", arg),
+            })
+        })
+        .collect::<Result<Vec<f32>, ImportError>>()?;
+
+    match values[..] {
+        [value] => Ok([value, value, value]),
+        [red, green, blue] => Ok([red, green, blue]),
+        _ => Err(ImportError::Parse {
+            line,
+            message: format!("expected 1 or 3 numbers, found {}", values.len()),
+        }),
+    }
+}
@@ -0,0 +1,176 @@
+// Wavefront OBJ parser. Only keeps what the renderer can use: positions, texture coordinates,
+// normals, polygon faces and which material each face uses.
+
+use super::ImportError;
+
+// Free-form curves and surfaces, we can't draw any of them
+const FREE_FORM_STATEMENTS: [&str; 18] = [
+    "cstype", "deg", "bmat", "step", "curv", "curv2", "surf", "parm", "trim", "hole", "scrv", "sp",
+    "end", "con", "bevel", "c_interp", "d_interp", "lod",
+];
+
+// Grouping and display statements that don't change the geometry
+const IGNORED_STATEMENTS: [&str; 6] = ["g", "s", "mg", "shadow_obj", "trace_obj", "vp"];
+
+#[derive(Default)]
+pub struct ObjData {
+    // From the first `o` statement
+    pub name: Option<String>,
+    pub material_libraries: Vec<String>,
+    pub positions: Vec<[f32; 3]>,
+    pub tex_coords: Vec<[f32; 2]>,
+    pub normals: Vec<[f32; 3]>,
+    pub faces: Vec<Face>,
+    // Every name used with `usemtl`, faces point into this
+    pub materials: Vec<String>,
+}
+
+pub struct Face {
+    pub corners: Vec<Corner>,
+    pub material: Option<usize>,
+}
+
+// Indices are already checked and start at 0
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Corner {
+    pub position: usize,
+    pub tex_coord: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+pub fn parse(text: &str, warnings: &mut Vec<String>) -> Result<ObjData, ImportError> {
+    let mut data = ObjData::default();
+    let mut material = None;
+    let mut skipped: Vec<&str> = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let line_number = number + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut parts = line.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        let args: Vec<&str> = parts.collect();
+
+        match keyword {
+            "v" => {
+                // Some exporters put a vertex color after the position, that gets ignored
+                let values = parse_floats(&args, 3, 7, line_number)?;
+                data.positions.push([values[0], values[1], values[2]]);
+            }
+            "vt" => {
+                let values = parse_floats(&args, 1, 3, line_number)?;
+                data.tex_coords.push([values[0], values.get(1).copied().unwrap_or(0.0)]);
+            }
+            "vn" => {
+                let values = parse_floats(&args, 3, 3, line_number)?;
+                data.normals.push([values[0], values[1], values[2]]);
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(ImportError::Parse {
+                        line: line_number,
+                        message: "a face needs at least 3 corners".to_string(),
+                    });
+                }
+                let corners = args
+                    .iter()
+                    .map(|corner| parse_corner(corner, &data, line_number))
+                    .collect::<Result<Vec<Corner>, ImportError>>()?;
+                data.faces.push(Face { corners, material });
+            }
+            "o" => {
+                if data.name.is_none() && !args.is_empty() {
+                    data.name = Some(args.join(" "));
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                material = Some(match data.materials.iter().position(|existing| *existing == name) {
+                    Some(index) => index,
+                    None => {
+                        data.materials.push(name);
+                        data.materials.len() - 1
+                    }
+                });
+            }
+            "mtllib" => data.material_libraries.extend(args.iter().map(|library| library.to_string())),
+            "p" | "l" => {
+                if !skipped.contains(&keyword) {
+                    skipped.push(keyword);
+                    warnings.push(format!("line {}: points and lines are not drawn, skipping them", line_number));
+                }
+            }
+            _ if FREE_FORM_STATEMENTS.contains(&keyword) => {
+                return Err(ImportError::Unsupported(format!(
+                    "free-form curves and surfaces ('{}' on line {})",
+                    keyword, line_number
+                )));
+            }
+            _ if IGNORED_STATEMENTS.contains(&keyword) => {}
+            _ => {
+                if !skipped.contains(&keyword) {
+                    skipped.push(keyword);
+                    warnings.push(format!("line {}: unknown statement '{}' skipped", line_number, keyword));
+                }
+            }
+        }
+    }
+
+    Ok(data)
+}
+
+// Between `min` and `max` numbers on one line
+fn parse_floats(args: &[&str], min: usize, max: usize, line: usize) -> Result<Vec<f32>, ImportError> {
+    if args.len() < min || args.len() > max {
+        return Err(ImportError::Parse {
+            line,
+            message: format!("expected {} to {} numbers, found {}", min, max, args.len()),
+        });
+    }
+
+    args.iter()
+        .map(|arg| {
+            arg.parse::<f32>().map_err(|_| ImportError::Parse {
+                line,
+                message: format!("'{}' is not a number", arg),
+            })
+        })
+        .collect()
+}
+
+// A face corner looks like `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn parse_corner(corner: &str, data: &ObjData, line: usize) -> Result<Corner, ImportError> {
+    let mut parts = corner.split('/');
+    let position = parts.next().unwrap_or("");
+    let tex_coord = parts.next().filter(|part| !part.is_empty());
+    let normal = parts.next().filter(|part| !part.is_empty());
+
+    if parts.next().is_some() || position.is_empty() {
+        return Err(ImportError::Parse {
+            line,
+            message: format!("'{}' is not a valid face corner", corner),
+        });
+    }
+
+    Ok(Corner {
+        position: resolve_index(position, data.positions.len(), line)?,
+        tex_coord: tex_coord.map(|index| resolve_index(index, data.tex_coords.len(), line)).transpose()?,
+        normal: normal.map(|index| resolve_index(index, data.normals.len(), line)).transpose()?,
+    })
+}
+
+// OBJ counts from 1, negative indices count back from the last element read so far
+fn resolve_index(index: &str, count: usize, line: usize) -> Result<usize, ImportError> {
+    let value: i64 = index.parse().map_err(|_| ImportError::Parse {
+        line,
+        message: format!("'{}' is not an index", index),
+    })?;
+
+    let resolved = if value < 0 { count as i64 + value } else { value - 1 };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(ImportError::IndexOutOfRange { line, index: value, count });
+    }
+
+    Ok(resolved as usize)
+}
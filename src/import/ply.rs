@@ -0,0 +1,428 @@
+// PLY importer, ASCII and binary in either byte order.
+// Reads positions, faces and, when the file has them, normals, texture coordinates and vertex colors.
+// Any other elements and properties are read past and forgotten.
+
+use super::{empty_mesh, ImportError};
+use crate::normals::{generate_normals, DEFAULT_CREASE_ANGLE};
+use crate::scene::Mesh;
+use crate::triangulate::triangulate;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // Integer colors go up to the largest value the type can hold, float ones are already 0 to 1.
+    // Negative values in signed types come out below 0 and get clamped with everything else.
+    fn color_range(self) -> f64 {
+        match self {
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+enum Property {
+    Scalar { name: String, kind: Scalar },
+    List { name: String, count: Scalar, item: Scalar },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+pub fn read(bytes: &[u8], name: &str) -> Result<Mesh, ImportError> {
+    let (format, elements, mut body) = read_header(bytes)?;
+
+    let mut mesh = empty_mesh(name, [0.0, 0.0, 0.0]);
+    let mut normals = vec![];
+    let mut faces: Vec<(usize, Vec<i64>)> = vec![];
+
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut position = [0.0; 3];
+            let mut normal = [None; 3];
+            let mut color = [None; 3];
+            let mut uv = [None; 2];
+
+            for property in &element.properties {
+                match property {
+                    Property::Scalar { name, kind } => {
+                        let value = body.read(*kind, format)?;
+                        if element.name != "vertex" {
+                            continue;
+                        }
+                        match name.as_str() {
+                            "x" => position[0] = value,
+                            "y" => position[1] = value,
+                            "z" => position[2] = value,
+                            "nx" => normal[0] = Some(value),
+                            "ny" => normal[1] = Some(value),
+                            "nz" => normal[2] = Some(value),
+                            "red" | "r" | "diffuse_red" => color[0] = Some(value / kind.color_range()),
+                            "green" | "g" | "diffuse_green" => color[1] = Some(value / kind.color_range()),
+                            "blue" | "b" | "diffuse_blue" => color[2] = Some(value / kind.color_range()),
+                            "s" | "u" | "texture_u" => uv[0] = Some(value),
+                            "t" | "v" | "texture_v" => uv[1] = Some(value),
+                            _ => {}
+                        }
+                    }
+                    Property::List { name, count, item } => {
+                        let length = body.read(*count, format)?;
+                        let values = (0..length as usize)
+                            .map(|_| body.read(*item, format))
+                            .collect::<Result<Vec<f64>, ImportError>>()?;
+
+                        if element.name == "face" && (name == "vertex_indices" || name == "vertex_index") {
+                            faces.push((body.line, values.iter().map(|value| *value as i64).collect()));
+                        }
+                    }
+                }
+            }
+
+            if element.name == "vertex" {
+                mesh.vertices.push((position[0] as f32, position[1] as f32, position[2] as f32));
+                if let [Some(x), Some(y), Some(z)] = normal {
+                    normals.push((x as f32, y as f32, z as f32));
+                }
+                if let [Some(red), Some(green), Some(blue)] = color {
+                    mesh.colors.push([red, green, blue].map(|channel| channel.clamp(0.0, 1.0) as f32));
+                }
+                if let [Some(u), Some(v)] = uv {
+                    mesh.uvs.push((u as f32, v as f32));
+                }
+            }
+        }
+    }
+
+    let vertex_count = mesh.vertices.len();
+    for (line, face) in &faces {
+        // Indices are kept signed until here so a negative one is reported instead of turning into 0
+        if let Some(index) = face.iter().find(|index| **index < 0 || **index as usize >= vertex_count) {
+            return Err(match format {
+                Format::Ascii => ImportError::IndexOutOfRange { line: *line, index: *index, count: vertex_count },
+                _ => ImportError::Invalid(format!("a face uses vertex {}, there are only {}", index, vertex_count)),
+            });
+        }
+
+        let points: Vec<[f32; 3]> = face
+            .iter()
+            .map(|index| {
+                let vertex = mesh.vertices[*index as usize];
+                [vertex.0, vertex.1, vertex.2]
+            })
+            .collect();
+        for triangle in triangulate(&points) {
+            mesh.indices.extend(triangle.map(|corner| face[corner] as u32));
+        }
+        mesh.face_count += 1;
+    }
+
+    // Only keep attributes every vertex has
+    if mesh.colors.len() != vertex_count {
+        mesh.colors.clear();
+    }
+    if mesh.uvs.len() != vertex_count {
+        mesh.uvs.clear();
+    }
+
+    if normals.len() == vertex_count {
+        mesh.normals = normals;
+    } else {
+        generate_normals(&mut mesh, DEFAULT_CREASE_ANGLE);
+    }
+
+    Ok(mesh)
+}
+
+fn read_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, Body<'_>), ImportError> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut offset = 0;
+    let mut line_number = 0;
+
+    loop {
+        let Some(length) = bytes[offset..].iter().position(|byte| *byte == b'\n') else {
+            return Err(ImportError::Invalid("the header never ends, end_header is missing".to_string()));
+        };
+        let line = String::from_utf8_lossy(&bytes[offset..offset + length]).into_owned();
+        offset += length + 1;
+        line_number += 1;
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let parse_error = |message: String| ImportError::Parse { line: line_number, message };
+
+        match parts[..] {
+            ["ply"] if line_number == 1 => {}
+            _ if line_number == 1 => return Err(ImportError::Invalid("not a PLY file".to_string())),
+            ["format", kind, _version] => {
+                format = Some(match kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(parse_error(format!("unknown format '{}'", kind))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| parse_error(format!("'{}' is not a count", count)))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, item, name] => {
+                let property = Property::List {
+                    name: name.to_string(),
+                    count: Scalar::from_name(count).ok_or_else(|| parse_error(format!("unknown type '{}'", count)))?,
+                    item: Scalar::from_name(item).ok_or_else(|| parse_error(format!("unknown type '{}'", item)))?,
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| parse_error("property before any element".to_string()))?
+                    .properties
+                    .push(property);
+            }
+            ["property", kind, name] => {
+                let property = Property::Scalar {
+                    name: name.to_string(),
+                    kind: Scalar::from_name(kind).ok_or_else(|| parse_error(format!("unknown type '{}'", kind)))?,
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| parse_error("property before any element".to_string()))?
+                    .properties
+                    .push(property);
+            }
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(parse_error(format!("unexpected '{}' in the header", line.trim()))),
+        }
+    }
+
+    let format = format.ok_or_else(|| ImportError::Invalid("the header has no format".to_string()))?;
+    let body = Body { bytes, offset, line: line_number + 1 };
+    check_elements(format, &elements, &body)?;
+
+    Ok((format, elements, body))
+}
+
+// The counts in the header come from the file. Checking them against what is left of it
+// keeps a made up count from looping or allocating forever.
+fn check_elements(format: Format, elements: &[Element], body: &Body) -> Result<(), ImportError> {
+    for element in elements {
+        if element.properties.is_empty() {
+            return Err(ImportError::Invalid(format!("element '{}' has no properties", element.name)));
+        }
+    }
+
+    if let Some(vertex) = elements.iter().find(|element| element.name == "vertex") {
+        for axis in ["x", "y", "z"] {
+            let declared = vertex
+                .properties
+                .iter()
+                .any(|property| matches!(property, Property::Scalar { name, .. } if name == axis));
+            if !declared {
+                return Err(ImportError::Invalid(format!("the vertices have no {} coordinate", axis)));
+            }
+        }
+    }
+
+    // Every scalar takes at least one token or its size in bytes, every list at least its count
+    let rest = &body.bytes[body.offset..];
+    let available = match format {
+        Format::Ascii => rest.split(u8::is_ascii_whitespace).filter(|token| !token.is_empty()).count(),
+        _ => rest.len(),
+    };
+
+    let mut needed: usize = 0;
+    for element in elements {
+        let per_item: usize = element
+            .properties
+            .iter()
+            .map(|property| match (format, property) {
+                (Format::Ascii, _) => 1,
+                (_, Property::Scalar { kind, .. }) => kind.size(),
+                (_, Property::List { count, .. }) => count.size(),
+            })
+            .sum();
+
+        needed = per_item
+            .checked_mul(element.count)
+            .and_then(|size| size.checked_add(needed))
+            .filter(|needed| *needed <= available)
+            .ok_or_else(|| {
+                ImportError::Invalid(format!(
+                    "the header says there are {} {} elements, the file is too short for that",
+                    element.count, element.name
+                ))
+            })?;
+    }
+
+    Ok(())
+}
+
+// Everything after the header. Values are read one after another no matter how the file is laid out.
+struct Body<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    // Only means something for ASCII files
+    line: usize,
+}
+
+impl Body<'_> {
+    fn read(&mut self, kind: Scalar, format: Format) -> Result<f64, ImportError> {
+        if format == Format::Ascii {
+            return self.read_ascii();
+        }
+
+        let Some(bytes) = self.bytes.get(self.offset..self.offset + kind.size()) else {
+            return Err(ImportError::Invalid("the file ends before all elements were read".to_string()));
+        };
+        self.offset += kind.size();
+
+        let big = format == Format::BigEndian;
+        macro_rules! number {
+            ($type:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                (if big { <$type>::from_be_bytes(bytes) } else { <$type>::from_le_bytes(bytes) }) as f64
+            }};
+        }
+
+        Ok(match kind {
+            Scalar::I8 => number!(i8),
+            Scalar::U8 => number!(u8),
+            Scalar::I16 => number!(i16),
+            Scalar::U16 => number!(u16),
+            Scalar::I32 => number!(i32),
+            Scalar::U32 => number!(u32),
+            Scalar::F32 => number!(f32),
+            Scalar::F64 => number!(f64),
+        })
+    }
+
+    fn read_ascii(&mut self) -> Result<f64, ImportError> {
+        while let Some(byte) = self.bytes.get(self.offset).filter(|byte| byte.is_ascii_whitespace()) {
+            if *byte == b'\n' {
+                self.line += 1;
+            }
+            self.offset += 1;
+        }
+
+        let start = self.offset;
+        while self.bytes.get(self.offset).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+            self.offset += 1;
+        }
+
+        if start == self.offset {
+            return Err(ImportError::Parse {
+                line: self.line,
+                message: "the file ends before all elements were read".to_string(),
+            });
+        }
+
+        let token = String::from_utf8_lossy(&self.bytes[start..self.offset]);
+        token.parse().map_err(|_| ImportError::Parse {
+            line: self.line,
+            message: format!("'{}' is not a number", token),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+                          element face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+    fn ascii(body: &str) -> Result<Mesh, ImportError> {
+        read(format!("{}{}", HEADER, body).as_bytes(), "ply")
+    }
+
+    #[test]
+    fn reads_ascii_files() {
+        let mesh = ascii("0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n").unwrap();
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn rejects_truncated_ascii_files() {
+        assert!(ascii("0 0 0\n1 0 0\n").is_err());
+        assert!(ascii("0 0 0\n1 0 0\n0 1 0\n3 0 1").is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_binary_files() {
+        let mut bytes = b"ply\nformat binary_little_endian 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nend_header\n".to_vec();
+        bytes.extend([0.0f32; 4].iter().flat_map(|value| value.to_le_bytes()));
+        assert!(matches!(read(&bytes, "ply"), Err(ImportError::Invalid(_))));
+    }
+
+    #[test]
+    fn rejects_counts_the_file_is_too_short_for() {
+        let header = "ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\nproperty float x\nproperty float y\nproperty float z\nend_header\n";
+        assert!(matches!(read(header.as_bytes(), "ply"), Err(ImportError::Invalid(_))));
+    }
+
+    #[test]
+    fn rejects_elements_without_properties() {
+        let text = "ply\nformat ascii 1.0\nelement junk 18446744073709551615\nend_header\n";
+        assert!(matches!(read(text.as_bytes(), "ply"), Err(ImportError::Invalid(_))));
+    }
+
+    #[test]
+    fn rejects_vertices_without_all_coordinates() {
+        let text = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nend_header\n0 0\n";
+        assert!(matches!(read(text.as_bytes(), "ply"), Err(ImportError::Invalid(_))));
+    }
+
+    #[test]
+    fn rejects_negative_face_indices() {
+        let result = ascii("0 0 0\n1 0 0\n0 1 0\n3 0 -1 2\n");
+        assert!(matches!(result, Err(ImportError::IndexOutOfRange { line: 13, index: -1, count: 3 })));
+    }
+}
@@ -0,0 +1,188 @@
+// STL importer, ASCII and binary. STL stores every triangle on its own with a copy of each
+// corner, so corners at the same position get welded back into shared vertices.
+
+use std::collections::HashMap;
+
+use super::{empty_mesh, ImportError};
+use crate::normals::{generate_normals, DEFAULT_CREASE_ANGLE};
+use crate::scene::Mesh;
+
+const HEADER_SIZE: usize = 80;
+// Normal, 3 corners and a 2 byte attribute
+const TRIANGLE_SIZE: usize = 50;
+
+pub fn read(bytes: &[u8], name: &str) -> Result<Mesh, ImportError> {
+    // Plenty of binary files start their header with "solid" too, so trust the size first.
+    // Some binary files have padding after the triangles, those are only taken as binary
+    // when they don't read as ASCII.
+    let binary_size = binary_size(bytes);
+    let fits_binary = binary_size.is_some_and(|size| size <= bytes.len());
+
+    let triangles = if binary_size == Some(bytes.len()) {
+        read_binary(bytes)
+    } else if bytes.trim_ascii_start().starts_with(b"solid") {
+        match read_ascii(&String::from_utf8_lossy(bytes)) {
+            Ok(triangles) if !triangles.is_empty() => triangles,
+            _ if fits_binary => read_binary(bytes),
+            result => result?,
+        }
+    } else if fits_binary {
+        read_binary(bytes)
+    } else {
+        return Err(ImportError::Invalid("not an ASCII or binary STL file".to_string()));
+    };
+
+    if triangles.is_empty() {
+        return Err(ImportError::Invalid("the file has no triangles".to_string()));
+    }
+
+    let mut mesh = empty_mesh(name, [0.0, 0.0, 0.0]);
+    let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+
+    for triangle in &triangles {
+        for corner in triangle {
+            let key = corner.map(f32::to_bits);
+            let index = *welded.entry(key).or_insert_with(|| {
+                mesh.vertices.push((corner[0], corner[1], corner[2]));
+                (mesh.vertices.len() - 1) as u32
+            });
+            mesh.indices.push(index);
+        }
+    }
+    mesh.face_count = triangles.len();
+
+    // The facet normals are flat anyway, smooth ones are worked out from the welded mesh
+    generate_normals(&mut mesh, DEFAULT_CREASE_ANGLE);
+
+    Ok(mesh)
+}
+
+// How long a binary file with the triangle count in its header is, None when there is no
+// header or the size doesn't fit in a usize
+fn binary_size(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < HEADER_SIZE + 4 {
+        return None;
+    }
+
+    // The count comes from the file, so on 32 bit targets the size it gives can overflow
+    let count = read_u32(bytes, HEADER_SIZE) as usize;
+    count.checked_mul(TRIANGLE_SIZE).and_then(|size| size.checked_add(HEADER_SIZE + 4))
+}
+
+// Only called once `binary_size` says the triangles fit, anything after them is ignored
+fn read_binary(bytes: &[u8]) -> Vec<[[f32; 3]; 3]> {
+    let count = read_u32(bytes, HEADER_SIZE) as usize;
+    bytes[HEADER_SIZE + 4..]
+        .chunks_exact(TRIANGLE_SIZE)
+        .take(count)
+        .map(|triangle| {
+            // Skip the normal at the start of each triangle
+            [0, 1, 2].map(|corner| {
+                let offset = 12 + corner * 12;
+                [
+                    read_f32(triangle, offset),
+                    read_f32(triangle, offset + 4),
+                    read_f32(triangle, offset + 8),
+                ]
+            })
+        })
+        .collect()
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn read_ascii(text: &str) -> Result<Vec<[[f32; 3]; 3]>, ImportError> {
+    let mut triangles = vec![];
+    let mut corners: Vec<[f32; 3]> = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let line_number = number + 1;
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("vertex") => {
+                let values = parts
+                    .map(|value| {
+                        value.parse::<f32>().map_err(|_| ImportError::Parse {
+                            line: line_number,
+                            message: format!("'{}' is not a number", value),
+                        })
+                    })
+                    .collect::<Result<Vec<f32>, ImportError>>()?;
+
+                let [x, y, z] = values[..] else {
+                    return Err(ImportError::Parse {
+                        line: line_number,
+                        message: format!("expected 3 numbers, found {}", values.len()),
+                    });
+                };
+                corners.push([x, y, z]);
+            }
+            Some("endloop") => {
+                let [a, b, c] = corners[..] else {
+                    return Err(ImportError::Unsupported(format!(
+                        "facets with {} corners (line {}), only triangles are allowed",
+                        corners.len(),
+                        line_number
+                    )));
+                };
+                triangles.push([a, b, c]);
+                corners.clear();
+            }
+            // solid, facet, outer loop, endfacet and endsolid only give structure
+            _ => {}
+        }
+    }
+
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A binary file with one triangle and `padding` bytes after it, the header starts with "solid"
+    fn binary(padding: usize) -> Vec<u8> {
+        let mut bytes = b"solid but binary".to_vec();
+        bytes.resize(HEADER_SIZE, b' ');
+        bytes.extend(1u32.to_le_bytes());
+        let floats = [0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0f32];
+        bytes.extend(floats.iter().flat_map(|value| value.to_le_bytes()));
+        bytes.extend([0, 0]);
+        bytes.resize(bytes.len() + padding, 0);
+        bytes
+    }
+
+    #[test]
+    fn reads_binary_files() {
+        let mesh = read(&binary(0), "triangle").unwrap();
+        assert_eq!(mesh.face_count, 1);
+        assert_eq!(mesh.vertices[1], (1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn reads_padded_binary_files_with_a_solid_header() {
+        let mesh = read(&binary(37), "triangle").unwrap();
+        assert_eq!(mesh.face_count, 1);
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn reads_ascii_files() {
+        let text = "solid t\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\nendfacet\nendsolid t\n";
+        let mesh = read(text.as_bytes(), "triangle").unwrap();
+        assert_eq!(mesh.face_count, 1);
+        assert_eq!(mesh.vertices[2], (0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn rejects_files_without_triangles() {
+        assert!(matches!(read(b"solid x\nendsolid\n", "empty"), Err(ImportError::Invalid(_))));
+    }
+}
@@ -0,0 +1,32 @@
+//! The lad engine renderer as a library.
+//!
+//! Nothing in here depends on egui or eframe, the window in `main.rs` is just one user of it.
+//! Build with `default-features = false` to leave the GUI dependencies out entirely.
+
+pub mod animation;
+pub mod camera;
+pub mod clipping;
+pub mod export;
+pub mod gizmo;
+pub mod history;
+pub mod import;
+pub mod lighting;
+pub mod material;
+pub mod math;
+pub mod normals;
+pub mod picking;
+pub mod rasterizer;
+pub mod render;
+pub mod scene;
+pub mod scene_file;
+pub mod texture;
+pub mod transform;
+pub mod triangulate;
+
+pub use camera::{Camera, CameraMode, Projection};
+pub use material::Material;
+pub use rasterizer::Framebuffer;
+pub use render::{CpuRenderer, RenderStats, Renderer, Shading};
+pub use scene::{CullMode, Light, LightKind, Mesh, Scene};
+pub use texture::{Texture, TextureFilter};
+pub use transform::{EulerOrder, Transform};
@@ -0,0 +1,196 @@
+use crate::camera::{Camera, Projection};
+use crate::material::Material;
+use crate::math::{calculate_normal, vec3_dot, vec3_normalize, vec3_sub};
+use crate::scene::{Light, LightKind};
+
+// Lights further away than this don't light anything
+const MAX_LIGHT_DISTANCE: f32 = 5000.0;
+
+// Where the surface is being looked at from, highlights depend on it
+#[derive(Clone, Copy, Debug)]
+pub enum Eye {
+    // Perspective cameras look out from a single point
+    Position([f32; 3]),
+    // Orthographic cameras look along the same direction everywhere
+    Direction([f32; 3]),
+}
+
+impl Eye {
+    pub fn from_camera(camera: &Camera) -> Eye {
+        match camera.projection {
+            Projection::Perspective => Eye::Position(camera.position),
+            Projection::Orthographic => Eye::Direction(camera.forward()),
+        }
+    }
+
+    // Unit vector from the point towards the viewer
+    fn direction_from(&self, point: [f32; 3]) -> [f32; 3] {
+        match self {
+            Eye::Position(position) => vec3_normalize(vec3_sub(*position, point)),
+            Eye::Direction(forward) => vec3_normalize([-forward[0], -forward[1], -forward[2]]),
+        }
+    }
+}
+
+// Light arriving at a point, split by whether a texture should tint it or not
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Illumination {
+    // Ambient and diffuse light, already colored by the material
+    pub diffuse: [f32; 3],
+    // Highlights and emissive light, these are added on top of the texture
+    pub specular: [f32; 3],
+}
+
+impl Illumination {
+    // The final RGB color for a surface with the given texture color, white leaves it untouched
+    pub fn color(&self, texel: [f32; 3]) -> [f32; 3] {
+        [
+            self.diffuse[0] * texel[0] + self.specular[0],
+            self.diffuse[1] * texel[1] + self.specular[1],
+            self.diffuse[2] * texel[2] + self.specular[2],
+        ]
+    }
+}
+
+pub fn calculate_lighting(
+    vertex_a: [f32; 3],
+    vertex_b: [f32; 3],
+    vertex_c: [f32; 3],
+    lights: &[Light],
+    material: &Material,
+    eye: Eye,
+) -> Illumination {
+    let normal = calculate_normal(vertex_a, vertex_b, vertex_c);
+
+    calculate_point_lighting(vertex_a, normal, lights, material, eye)
+}
+
+// Lighting of a single point with a known normal, smooth shading calls this per vertex or per pixel.
+// Every light is worked out on its own and the results are added together.
+pub fn calculate_point_lighting(
+    point: [f32; 3],
+    normal: [f32; 3],
+    lights: &[Light],
+    material: &Material,
+    eye: Eye,
+) -> Illumination {
+    let mut ambient = [0.0, 0.0, 0.0];
+    let mut diffuse = [0.0, 0.0, 0.0];
+    let mut specular = [0.0, 0.0, 0.0];
+
+    let to_eye = eye.direction_from(point);
+
+    for light in lights {
+        // Same everywhere, doesn't care about which way the surface faces
+        if light.kind == LightKind::Ambient {
+            for (total, channel) in ambient.iter_mut().zip(light.color) {
+                *total += light.intensity * channel;
+            }
+            continue;
+        }
+
+        let Some((to_light, strength)) = incoming_light(light, point) else {
+            continue;
+        };
+
+        // Calculate the cosine of the angle between the normal and the to_light vector
+        let cos_theta = vec3_dot(normal, to_light);
+
+        if cos_theta <= 0.0 {
+            // Light is behind the surface, no lighting
+            continue;
+        }
+
+        // Blinn-Phong: the closer the normal is to halfway between the light and the eye,
+        // the brighter the highlight
+        let half = vec3_normalize([to_light[0] + to_eye[0], to_light[1] + to_eye[1], to_light[2] + to_eye[2]]);
+        let highlight = vec3_dot(normal, half).max(0.0).powf(material.shininess);
+
+        for i in 0..3 {
+            //Lambert's Cosine Law
+            diffuse[i] += strength * cos_theta * light.color[i];
+            specular[i] += strength * highlight * light.color[i];
+        }
+    }
+
+    let mut illumination = Illumination { diffuse: [0.0; 3], specular: material.emissive };
+    for i in 0..3 {
+        illumination.diffuse[i] += ambient[i] * material.ambient[i] + diffuse[i] * material.diffuse[i];
+        illumination.specular[i] += specular[i] * material.specular[i] * material.specular_strength;
+    }
+    illumination
+}
+
+// The direction towards a light and how strong it is when it arrives at the point.
+// Nothing when the light can't reach the point at all.
+fn incoming_light(light: &Light, point: [f32; 3]) -> Option<([f32; 3], f32)> {
+    match light.kind {
+        LightKind::Ambient => None,
+
+        LightKind::Directional => {
+            // The light travels along `direction`, so the light is found the other way
+            let to_light = vec3_normalize([-light.direction[0], -light.direction[1], -light.direction[2]]);
+            Some((to_light, light.intensity))
+        }
+
+        LightKind::Point | LightKind::Spot => {
+            // Calculate the vector from the point to the light source
+            let to_light = vec3_sub(light.position, point);
+
+            // Calculate the distance from the light source to the point
+            let distance = vec3_dot(to_light, to_light).sqrt();
+
+            if distance > MAX_LIGHT_DISTANCE || distance == 0.0 {
+                // Light is too far away, no lighting
+                return None;
+            }
+
+            let to_light_normalized = [
+                to_light[0] / distance,
+                to_light[1] / distance,
+                to_light[2] / distance,
+            ];
+
+            let [constant, linear, quadratic] = light.attenuation;
+            let attenuation = constant + linear * distance + quadratic * distance * distance;
+            if attenuation <= 0.0 {
+                return None;
+            }
+
+            let mut strength = light.intensity / attenuation;
+
+            if light.kind == LightKind::Spot {
+                strength *= spot_factor(light, to_light_normalized);
+            }
+
+            Some((to_light_normalized, strength))
+        }
+    }
+}
+
+// 1 inside the inner cone, 0 outside the outer cone and a smooth falloff in between
+fn spot_factor(light: &Light, to_light: [f32; 3]) -> f32 {
+    let direction = vec3_normalize(light.direction);
+    let cos_angle = -vec3_dot(direction, to_light);
+
+    let cos_inner = light.inner_angle.to_radians().cos();
+    let cos_outer = light.outer_angle.max(light.inner_angle).to_radians().cos();
+
+    if cos_angle >= cos_inner {
+        return 1.0;
+    }
+    if cos_angle <= cos_outer || cos_inner == cos_outer {
+        return 0.0;
+    }
+
+    let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Lighting can go past 1, anything brighter than that just ends up white
+pub fn color_to_rgba(color: [f32; 3], opacity: f32) -> [u8; 4] {
+    let [red, green, blue] = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    let alpha = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    [red, green, blue, alpha]
+}
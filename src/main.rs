@@ -2,37 +2,32 @@
 
 use eframe::egui;
 use egui::*;
-use std::fs::File;
-use std::io::{BufReader, Cursor};
-use obj::{load_obj, Obj};
+use std::path::Path;
+use std::sync::Arc;
 
+use lad_engine_rust::animation::{animate_scene, Animated, Interpolation, KEY_TOLERANCE};
+use lad_engine_rust::export::export_meshes;
+use lad_engine_rust::history::{Command, History, ObjectMoves, ObjectState};
+use lad_engine_rust::math::{mat4_identity, mat4_inverse, mat4_mul, quat_from_axis_angle, quat_mul, quat_normalize, vec3_add, vec3_dot, vec3_scale, vec3_sub, Quat};
+use lad_engine_rust::gizmo::{GizmoDrag, GizmoFrame, GizmoMode, GizmoSpace, Snapping};
+use lad_engine_rust::picking::{pick, screen_ray, world_to_screen, Ray};
+use lad_engine_rust::import::{import_mesh_with_warnings, ImportSource};
+use lad_engine_rust::scene_file::{load_scene, save_scene, scene_from_bytes, ViewState};
+use lad_engine_rust::{Camera, CameraMode, CpuRenderer, CullMode, Light, LightKind, Material, Projection, Renderer, Scene, Shading, Texture, TextureFilter, Transform, EulerOrder};
 
 
-struct Scene {
-    camera_position : [f32; 3],
-    camera_rotation : [f32; 3],
-    objects: Vec<Mesh>,
-    light: Light,
-}
-
-struct Light {
-    position: [f32; 3],
-    intensity: f32,
-}
-
-#[derive(Debug)] 
-struct Mesh {
-    name: String,
-    vertices: Vec<(f32, f32, f32)>,
-    indices: Vec<u32>,
-    position: [f32; 3],
-    rotation: [f32; 3], 
-}
-
-
+fn main() -> Result<(), eframe::Error> {
+    let args: Vec<String> = std::env::args().collect();
 
+    // `lad_engine_rust render [scene.obj] --out frame.png --size 1280x720` renders without a window
+    if args.get(1).map(String::as_str) == Some("render") {
+        if let Err(err) = headless_render(&args[2..]) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-fn main() -> Result<(), eframe::Error> {
    let options = eframe::NativeOptions {
     icon_data: Some(load_icon()),
     drag_and_drop_support: true,
@@ -46,265 +41,1104 @@ fn main() -> Result<(), eframe::Error> {
     )
 }
 
+const RENDER_USAGE: &str = "usage: lad_engine_rust render [scene.obj|scene.json] [--out frame.png] [--size 1280x720] [--shading flat|gouraud|phong] [--time seconds]";
+
+// Renders a single frame straight to a png, no window or GPU needed
+fn headless_render(args: &[String]) -> Result<(), String> {
+    let mut scene_path = None;
+    let mut out_path = "frame.png".to_string();
+    let mut size = (1280, 720);
+    let mut shading = None;
+    let mut time = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" | "-o" => {
+                out_path = args.next().ok_or(RENDER_USAGE)?.clone();
+            }
+            "--size" | "-s" => {
+                let value = args.next().ok_or(RENDER_USAGE)?;
+                size = parse_size(value).ok_or_else(|| format!("invalid size '{}', expected WIDTHxHEIGHT", value))?;
+            }
+            "--shading" => {
+                shading = Some(match args.next().ok_or(RENDER_USAGE)?.as_str() {
+                    "flat" => Shading::Flat,
+                    "gouraud" => Shading::Gouraud,
+                    "phong" => Shading::Phong,
+                    other => return Err(format!("unknown shading '{}', expected flat, gouraud or phong", other)),
+                });
+            }
+            "--time" | "-t" => {
+                let value = args.next().ok_or(RENDER_USAGE)?;
+                time = Some(value.parse::<f32>().map_err(|_| format!("invalid time '{}', expected seconds", value))?);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'\n{}", arg, RENDER_USAGE)),
+            _ => scene_path = Some(arg.clone()),
+        }
+    }
+
+    // Without a file we render the same scene the window starts with
+    let mut scene = Scene::default();
+    let mut view = ViewState::default();
+    if let Some(path) = scene_path.as_deref().filter(|path| is_scene(path)) {
+        // Saved scenes come with their own camera and lights
+        (scene, view) = load_scene(Path::new(path)).map_err(|err| format!("couldn't open {}: {}", path, err))?;
+    } else if let Some(path) = scene_path {
+        let mut warnings = vec![];
+        scene.objects = import_mesh_with_warnings(ImportSource::Path(Path::new(&path)), &mut warnings)
+            .map_err(|err| format!("couldn't import {}: {}", path, err))?;
+        for warning in warnings {
+            eprintln!("warning: {}", warning);
+        }
+    }
+
+    // Without a time the scene is rendered as it was saved
+    if let Some(time) = time {
+        animate_scene(&mut scene, time);
+    }
+
+    let mut renderer = CpuRenderer::new();
+    renderer.shading = shading.unwrap_or(view.shading);
+    let framebuffer = renderer.render(&scene, size.0 as usize, size.1 as usize);
+
+    let image = image::RgbaImage::from_raw(size.0, size.1, framebuffer.color.clone())
+        .ok_or("framebuffer size does not match the image size")?;
+    image
+        .save(&out_path)
+        .map_err(|err| format!("Error writing {}: {}", out_path, err))?;
+
+    let stats = renderer.stats();
+    println!(
+        "Rendered {}x{} to {} ({} triangles, {} culled)",
+        size.0, size.1, out_path, stats.triangles, stats.culled
+    );
+    Ok(())
+}
+
+fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once(['x', 'X'])?;
+    let width = width.trim().parse().ok()?;
+    let height = height.trim().parse().ok()?;
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+// Older entries get dropped once the log is this long
+const MAX_LOG_ENTRIES: usize = 100;
+
+#[derive(Clone, Copy, PartialEq)]
+enum LogLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+struct LogEntry {
+    level: LogLevel,
+    message: String,
+}
+
 struct Content {
-    text: String,
     current_scene: Scene,
     speed_slider: (f32, f32, f32),
     selected_object: Option<usize>,
     rotation_index: Option<usize>,
     dropped_files: Vec<egui::DroppedFile>,
+    renderer: CpuRenderer,
+    viewport_texture: Option<TextureHandle>,
+    log: Vec<LogEntry>,
+    // Export settings, the format comes from the extension of the path
+    export_path: String,
+    export_all: bool,
+    bake_transforms: bool,
+    // Where File > Save and Open read and write
+    scene_path: String,
+    history: History,
+    // Set while a drag or text edit that already made an undo step is still going
+    continuing_edit: bool,
+    // The scene was swapped or stepped through the history during the UI, so there is nothing to diff
+    skip_edit_diff: bool,
+    // Object being dragged around the scene view to attach it to another
+    dragged_object: Option<usize>,
+    // The order rotation angles are shown and typed in
+    euler_order: EulerOrder,
+    // Angles last shown for an object and the rotation they belonged to. Reading angles back
+    // out of a rotation can give a different set that means the same, so these stay on screen
+    // until something else turns the object.
+    euler_angles: Option<(usize, Quat, [f32; 3])>,
+    camera_mode: CameraMode,
+    // How far in front of the camera the point it orbits around is
+    orbit_distance: f32,
+    // The mouse button held down since a press on the viewport, that drag moves the camera
+    camera_drag: Option<PointerButton>,
+    // Where the settings were drawn last frame, the camera ignores the pointer there
+    settings_rect: Rect,
+    // Handles drawn over the selected object
+    gizmo_mode: GizmoMode,
+    gizmo_space: GizmoSpace,
+    snapping: Snapping,
+    // The handle being dragged and the object as it was before, for undo
+    gizmo_drag: Option<(GizmoDrag, ObjectState)>,
+    // Timeline position in seconds
+    time: f32,
+    playing: bool,
+    looping: bool,
+    // The time was moved by hand, so the scene has to catch up even though nothing is playing
+    time_moved: bool,
+    key_target: KeyTarget,
+}
+
+// What the timeline keys
+#[derive(Clone, Copy, PartialEq)]
+enum KeyTarget {
+    SelectedObject,
+    Camera,
+    Light(usize),
+}
+
+// One handle of the gizmo as it shows up on screen, a line or a closed ring
+struct GizmoHandle {
+    axis: usize,
+    points: Vec<Pos2>,
+}
+
+// Length of the gizmo's handles on screen, in points. It stays the same however far away the object is.
+const GIZMO_SIZE: f32 = 90.0;
+// How close the pointer has to be to a handle to grab it
+const GIZMO_GRAB_DISTANCE: f32 = 8.0;
+
+// What the settings could change this frame, compared afterwards to see what did
+struct EditSnapshot {
+    camera: Camera,
+    lights: Vec<Light>,
+    object_count: usize,
+    object: Option<(usize, ObjectState)>,
 }
 
 impl Default for Content {
     fn default() -> Self {
         Content {
-            text: String::new(),
             current_scene: Scene::default(),
             speed_slider: (0.0, 10.0, 0.0), 
             selected_object: Some(0),
             rotation_index: Some(0),
             dropped_files: vec!(),
+            renderer: CpuRenderer::new(),
+            viewport_texture: None,
+            log: vec![],
+            export_path: "export.obj".to_string(),
+            export_all: false,
+            bake_transforms: true,
+            scene_path: "scene.json".to_string(),
+            history: History::new(),
+            continuing_edit: false,
+            skip_edit_diff: false,
+            dragged_object: None,
+            euler_order: EulerOrder::default(),
+            euler_angles: None,
+            camera_mode: CameraMode::default(),
+            orbit_distance: 10.0,
+            camera_drag: None,
+            settings_rect: Rect::NOTHING,
+            gizmo_mode: GizmoMode::default(),
+            gizmo_space: GizmoSpace::default(),
+            snapping: Snapping::default(),
+            gizmo_drag: None,
+            time: 0.0,
+            playing: false,
+            looping: false,
+            time_moved: false,
+            key_target: KeyTarget::SelectedObject,
         }
     }
 }
 
 
+impl eframe::App for Content {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let delta_time = ctx.input(|ctx| ctx.stable_dt);
 
-impl Default for Scene {
-    fn default() -> Self {
-        Scene {
-            camera_position: [0.0, 0.0, 0.0],
-            camera_rotation: [0.0, 0.0, 0.0],
-            objects: vec![
-                obj_to_mesh(include_bytes!("models/suzanne.obj"), [1.6, 0.7, -1.3], "Suzanne"), 
-                obj_to_mesh(include_bytes!("models/mario.obj"), [0.0, 0.0, 0.0], "Mario")
-                ],
-            light: Light {position: [4.8, 5.1, 4.5], intensity: 29.3},
-        }
+        self.handle_viewport_input(ctx, delta_time);
+        self.handle_shortcuts(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // -----------------------------
+            // * drag and drop handling *
+            self.handle_dropped_files();
+
+            preview_files_being_dropped(ctx);
+
+            ctx.input(|i| {
+                if !i.raw.dropped_files.is_empty() {
+                    self.dropped_files = i.raw.dropped_files.clone();
+                }
+            });
+
+            // * Drag and drop ^
+            // -----------------------
+
+            self.paint_viewport(ui);
+            self.paint_gizmo(ui);
+
+
+            let before = self.edit_snapshot();
+
+            let settings = Frame::popup(ui.style())
+            .stroke(Stroke::NONE)
+            .show(ui, |ui| {
+                ui.set_max_width(170.0);
+                file_menu(ui, self);
+                CollapsingHeader::new("Settings")
+                .show(ui, |ui| settings_menu(ui, self, _frame))
+            });
+            self.settings_rect = settings.response.rect;
+
+            self.record_edits(ctx, before);
+
+            if let Some(index) = self.rotation_index {
+                if let Some(object) = self.current_scene.objects.get_mut(index) {
+                    // The three speeds make up one axis to turn around, so spinning on several at once never locks up
+                    let speed = [self.speed_slider.0, self.speed_slider.1, self.speed_slider.2].map(|speed| (speed * 10.0).to_radians());
+                    let angle = vec3_dot(speed, speed).sqrt() * delta_time;
+                    if angle > 0.0 {
+                        let rotation = &mut object.transform.rotation;
+                        *rotation = quat_normalize(quat_mul(quat_from_axis_angle(speed, angle), *rotation));
+                    }
+                }
+            }
+            
+        });
+
+        log_window(ctx, &mut self.log);
+        timeline_window(ctx, self);
+        self.advance_animation(delta_time);
+        ctx.request_repaint();
     }
 }
 
-// Handles obj to our mesh format for include_bytes!
-fn obj_to_mesh(bytes:&'static [u8], position: [f32; 3], name: &str) -> Mesh {
-    let OBJ_BYTES: &'static [u8] = bytes;
+impl Content {
+    // Rasterizes the scene on the CPU and shows the result as a full screen texture
+    fn paint_viewport(&mut self, ui: &Ui) {
+        let canvas = ui.ctx().screen_rect();
+        self.renderer.highlight = self.selected_object;
+        let framebuffer = self.renderer.render(
+            &self.current_scene,
+            canvas.width() as usize,
+            canvas.height() as usize,
+        );
 
-    let obj_bytes = Cursor::new(OBJ_BYTES);
-    let input = BufReader::new(obj_bytes);
-    let mesh: Obj = load_obj(input).expect("AAAA");
-    
-    let mut mesh_vertices = vec![];  
-    let mut mesh_indices = vec![];  
+        let image = ColorImage::from_rgba_unmultiplied(
+            [framebuffer.width, framebuffer.height],
+            &framebuffer.color,
+        );
 
-    for index in &mesh.indices {
-        mesh_indices.push(*index as u32); 
+        let texture = match &mut self.viewport_texture {
+            Some(texture) => {
+                texture.set(image, TextureOptions::LINEAR);
+                texture
+            }
+            None => self
+                .viewport_texture
+                .insert(ui.ctx().load_texture("viewport", image, TextureOptions::LINEAR)),
+        };
+
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        ui.painter().image(texture.id(), canvas, uv, Color32::WHITE);
     }
 
-    for vertex in &mesh.vertices {
-        let position = vertex.position;
-        let mesh_vertex = (position[0] as f32, position[1] as f32, position[2] as f32);
-        mesh_vertices.push(mesh_vertex);
+    // Where the pointer is, as long as it is over the rendered image and not over the settings
+    // or a window on top of it
+    fn pointer_over_viewport(&self, ctx: &Context) -> Option<Pos2> {
+        ctx.pointer_hover_pos().filter(|pos| {
+            !self.settings_rect.contains(*pos) && ctx.layer_id_at(*pos).is_none_or(|layer| layer == LayerId::background())
+        })
     }
 
-    let output = Mesh {
-        name: name.to_string(),
-        vertices: mesh_vertices,  
-        indices: mesh_indices,   
-        position,
-        rotation: [0.0, 0.0, 0.0],
-    };
+    // Mouse and keyboard camera controls, the gizmo, and clicking objects to select them
+    fn handle_viewport_input(&mut self, ctx: &Context, delta_time: f32) {
+        let pointer = self.pointer_over_viewport(ctx);
+        let hovered = pointer.is_some();
+        let screen = ctx.screen_rect();
 
-    output
-}
+        // While a handle is dragged the mouse does nothing else
+        if self.drag_gizmo(ctx, screen) {
+            return;
+        }
 
-//handles obj to our mesh for web builds
-fn drag_to_mesh(bytes: &Option<std::sync::Arc<[u8]>>, position: [f32; 3], name: &str) -> Mesh {
-    let mut output = Mesh {
-        name: "error".to_string(),
-        vertices: vec![],
-        indices: vec![],
-        position,
-        rotation: [0.0, 0.0, 0.0],
-    };
+        // A click that didn't turn into a drag picks the object under the pointer
+        let clicked = ctx.input(|i| i.pointer.primary_clicked().then(|| i.pointer.interact_pos()).flatten());
+        if let Some(pos) = clicked.filter(|_| hovered) {
+            let ray = viewport_ray(&self.current_scene.camera, pos, screen);
+            self.selected_object = pick(&self.current_scene, &ray).map(|hit| hit.object);
+        }
+
+        let (pressed, delta, scroll) = ctx.input(|i| {
+            let pressed = [PointerButton::Primary, PointerButton::Middle, PointerButton::Secondary]
+                .into_iter()
+                .find(|button| i.pointer.button_pressed(*button));
+            (pressed, i.pointer.delta(), i.scroll_delta.y)
+        });
+
+        // Pressing on a handle grabs it instead of turning the camera
+        if let (Some(PointerButton::Primary), Some(pos), None) = (pressed, pointer, self.camera_drag) {
+            if let Some((object, axis)) = self.selected_object.zip(self.gizmo_handle_at(pos, screen)) {
+                let ray = viewport_ray(&self.current_scene.camera, pos, screen);
+                let scene = &self.current_scene;
+                if let Some(drag) = GizmoDrag::start(scene, object, self.gizmo_mode, self.gizmo_space, axis, &ray) {
+                    self.gizmo_drag = Some((drag, ObjectState::of(&scene.objects[object])));
+                    return;
+                }
+            }
+        }
+
+        // A drag keeps going when the pointer wanders over the settings, but has to start on the viewport
+        if let Some(button) = pressed {
+            if hovered && self.camera_drag.is_none() {
+                self.camera_drag = Some(button);
+            }
+        }
+        if let Some(button) = self.camera_drag {
+            if !ctx.input(|i| i.pointer.button_down(button)) {
+                self.camera_drag = None;
+            }
+        }
+
+        let camera = &mut self.current_scene.camera;
+        match self.camera_mode {
+            CameraMode::Orbit => {
+                match self.camera_drag {
+                    Some(PointerButton::Primary) => camera.orbit(self.orbit_distance, delta.x * 0.3, delta.y * 0.3),
+                    Some(PointerButton::Middle) => {
+                        // World units per pixel at the target, so it sticks to the pointer
+                        let view_height = match camera.projection {
+                            Projection::Perspective => 2.0 * self.orbit_distance * (camera.fov.to_radians() / 2.0).tan(),
+                            Projection::Orthographic => camera.ortho_height,
+                        };
+                        let scale = view_height / ctx.screen_rect().height().max(1.0);
+                        camera.pan(-delta.x * scale, delta.y * scale);
+                    }
+                    _ => {}
+                }
+                if hovered && scroll != 0.0 {
+                    self.orbit_distance = camera.zoom(self.orbit_distance, (-scroll * 0.002).exp());
+                }
+            }
+            CameraMode::Fly => {
+                if self.camera_drag == Some(PointerButton::Secondary) {
+                    camera.turn(delta.x * 0.2, delta.y * 0.2);
+                }
+
+                // Keys only fly the camera while the pointer is on the viewport or turning it, and
+                // not while a text field, combo box or any other widget has the keyboard
+                let widget_focused = ctx.wants_keyboard_input() || ctx.memory(|m| m.focus().is_some() || m.any_popup_open());
+                if widget_focused || !(hovered || self.camera_drag.is_some()) {
+                    return;
+                }
+                let key = |key: Key| if ctx.input(|i| i.key_down(key)) { 1.0 } else { 0.0 };
+                let speed = if ctx.input(|i| i.modifiers.shift) { 30.0 } else { 10.0 };
+                let step = speed * delta_time;
+
+                let forward = vec3_scale(camera.forward(), (key(Key::W) - key(Key::S)) * step);
+                let right = vec3_scale(camera.right(), (key(Key::D) - key(Key::A)) * step);
+                let up = [0.0, (key(Key::E) - key(Key::Q)) * step, 0.0];
+                camera.position = vec3_add(camera.position, vec3_add(forward, vec3_add(right, up)));
+
+                camera.turn((key(Key::ArrowRight) - key(Key::ArrowLeft)) * 120.0 * delta_time, 0.0);
+            }
+        }
+    }
+
+    // Moves the object along with a handle being dragged, and makes it one undo step once the
+    // button is let go. Returns whether a handle was being dragged.
+    fn drag_gizmo(&mut self, ctx: &Context, screen: Rect) -> bool {
+        let Some((drag, before)) = &mut self.gizmo_drag else {
+            return false;
+        };
+
+        let (down, pos, command) = ctx.input(|i| (i.pointer.primary_down(), i.pointer.interact_pos(), i.modifiers.command));
+        if down {
+            // Ctrl turns snapping on or off for as long as it is held
+            let mut snapping = self.snapping;
+            snapping.enabled ^= command;
 
-    if let Some(data) = bytes.as_ref().map(|data| data.as_ref()) {
-        let obj_bytes = Cursor::new(data);
-        let input = BufReader::new(obj_bytes);
+            if let Some(pos) = pos {
+                let ray = viewport_ray(&self.current_scene.camera, pos, screen);
+                if let (Some(transform), Some(object)) = (drag.update(&ray, snapping), self.current_scene.objects.get_mut(drag.object)) {
+                    object.transform = transform;
+                }
+            }
+            return true;
+        }
+
+        let index = drag.object;
+        let before = before.clone();
+        self.gizmo_drag = None;
+        if self.current_scene.objects.get(index).is_some_and(|object| ObjectState::of(object) != before) {
+            self.history.record(Command::SetObject { index, state: before });
+        }
+        true
+    }
 
-        // Use the '?' operator to handle the potential error from load_obj
-        let mesh: Obj = match load_obj(input) {
-            Ok(mesh) => mesh,
-            Err(error) => {
-                eprintln!("Error loading OBJ: {:?}", error);
-                return output; // Return early on error
+    // The handles of the selected object, empty when there is nothing to show them on
+    fn gizmo_handles(&self, screen: Rect) -> Vec<GizmoHandle> {
+        let scene = &self.current_scene;
+        let Some(object) = self.selected_object.filter(|index| *index < scene.objects.len()) else {
+            return vec![];
+        };
+        let frame = GizmoFrame::of(scene, object, self.gizmo_mode, self.gizmo_space);
+        let camera = &scene.camera;
+
+        // World units per point where the object is
+        let world_height = match camera.projection {
+            Projection::Perspective => {
+                let depth = vec3_dot(vec3_sub(frame.center, camera.position), camera.forward());
+                if depth <= camera.near {
+                    return vec![];
+                }
+                2.0 * depth * (camera.fov.to_radians() / 2.0).tan()
             }
+            Projection::Orthographic => camera.ortho_height,
+        };
+        let length = GIZMO_SIZE * world_height / screen.height().max(1.0);
+
+        let to_screen = |point: [f32; 3]| {
+            world_to_screen(camera, point, screen.width(), screen.height()).map(|[x, y]| pos2(x + screen.min.x, y + screen.min.y))
+        };
+        let along = |direction: [f32; 3], distance: f32| vec3_add(frame.center, vec3_scale(direction, distance));
+
+        (0..3)
+            .filter_map(|axis| {
+                let points: Vec<[f32; 3]> = match self.gizmo_mode {
+                    GizmoMode::Translate | GizmoMode::Scale => vec![frame.center, along(frame.axes[axis], length)],
+                    GizmoMode::Rotate => {
+                        let u = frame.axes[(axis + 1) % 3];
+                        let v = frame.axes[(axis + 2) % 3];
+                        (0..48)
+                            .map(|step| {
+                                let angle = step as f32 / 48.0 * std::f32::consts::TAU;
+                                vec3_add(along(u, angle.cos() * length), vec3_scale(v, angle.sin() * length))
+                            })
+                            .collect()
+                    }
+                };
+                let points = points.into_iter().map(to_screen).collect::<Option<Vec<Pos2>>>()?;
+                Some(GizmoHandle { axis, points })
+            })
+            .collect()
+    }
+
+    // The axis of the handle closest to `pos`, if it is close enough to grab
+    fn gizmo_handle_at(&self, pos: Pos2, screen: Rect) -> Option<usize> {
+        let closed = self.gizmo_mode == GizmoMode::Rotate;
+        self.gizmo_handles(screen)
+            .iter()
+            .map(|handle| (handle.axis, distance_to_polyline(pos, &handle.points, closed)))
+            .filter(|(_, distance)| *distance <= GIZMO_GRAB_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(axis, _)| axis)
+    }
+
+    fn paint_gizmo(&self, ui: &Ui) {
+        let screen = ui.ctx().screen_rect();
+        let handles = self.gizmo_handles(screen);
+        let Some(center) = handles.first().map(|handle| handle.points[0]) else {
+            return;
         };
 
-        let mut mesh_vertices = vec![];
-        let mut mesh_indices = vec![];
+        // The handle being dragged lights up, or the one the pointer is over when nothing is
+        let active = match &self.gizmo_drag {
+            Some((drag, _)) => Some(drag.axis),
+            None => self.pointer_over_viewport(ui.ctx()).and_then(|pos| self.gizmo_handle_at(pos, screen)),
+        };
+        let painter = ui.painter();
+
+        for handle in &handles {
+            let color = if active == Some(handle.axis) {
+                Color32::from_rgb(255, 210, 60)
+            } else {
+                [Color32::from_rgb(230, 70, 70), Color32::from_rgb(90, 200, 90), Color32::from_rgb(80, 130, 240)][handle.axis]
+            };
+            let stroke = Stroke::new(3.0, color);
+
+            match self.gizmo_mode {
+                GizmoMode::Rotate => {
+                    painter.add(Shape::closed_line(handle.points.clone(), stroke));
+                }
+                GizmoMode::Translate => {
+                    let (start, end) = (handle.points[0], handle.points[1]);
+                    painter.line_segment([start, end], stroke);
+                    // An axis pointing at the camera has no direction on screen to point the tip in
+                    let direction = end - start;
+                    if direction.length() > 1.0 {
+                        let direction = direction.normalized();
+                        let side = direction.rot90() * 6.0;
+                        painter.add(Shape::convex_polygon(vec![end + direction * 14.0, end + side, end - side], color, Stroke::NONE));
+                    }
+                }
+                GizmoMode::Scale => {
+                    let (start, end) = (handle.points[0], handle.points[1]);
+                    painter.line_segment([start, end], stroke);
+                    painter.rect_filled(Rect::from_center_size(end, vec2(10.0, 10.0)), 0.0, color);
+                }
+            }
+        }
+        painter.circle_filled(center, 3.0, Color32::WHITE);
+    }
 
-        for index in &mesh.indices {
-            mesh_indices.push(*index as u32);
+    // Moves the timeline along while playing and puts the scene where its keyframes say.
+    // Otherwise the keyframes leave everything alone, so it can be moved around and keyed again.
+    fn advance_animation(&mut self, delta_time: f32) {
+        let length = self.current_scene.animation_length;
+        if self.playing {
+            self.time += delta_time;
+            if self.time > length {
+                if self.looping && length > 0.0 {
+                    self.time = self.time.rem_euclid(length);
+                } else {
+                    self.time = length;
+                    self.playing = false;
+                }
+            }
         }
+        self.time = self.time.clamp(0.0, length);
 
-        for vertex in &mesh.vertices {
-            let position = vertex.position;
-            let mesh_vertex = (position[0] as f32, position[1] as f32, position[2] as f32);
-            mesh_vertices.push(mesh_vertex);
+        if self.playing || std::mem::take(&mut self.time_moved) {
+            animate_scene(&mut self.current_scene, self.time);
         }
+    }
 
-        output = Mesh {
-            name: name.to_string(),
-            vertices: mesh_vertices,
-            indices: mesh_indices,
-            position,
-            rotation: [0.0, 0.0, 0.0],
+    // What the timeline keys, None when that is gone or nothing is selected
+    fn key_target(&mut self) -> Option<&mut dyn Animated> {
+        let scene = &mut self.current_scene;
+        match self.key_target {
+            KeyTarget::SelectedObject => self.selected_object.and_then(|index| scene.objects.get_mut(index)).map(|mesh| mesh as &mut dyn Animated),
+            KeyTarget::Camera => Some(&mut scene.camera),
+            KeyTarget::Light(index) => scene.lights.get_mut(index).map(|light| light as &mut dyn Animated),
+        }
+    }
+
+    // Changes the keyframes of whatever the timeline keys, as one undo step
+    fn edit_keys(&mut self, edit: impl FnOnce(&mut dyn Animated)) {
+        let scene = &mut self.current_scene;
+        let undo = match self.key_target {
+            KeyTarget::SelectedObject => {
+                let Some((index, mesh)) = self.selected_object.and_then(|index| Some(index).zip(scene.objects.get_mut(index))) else {
+                    return;
+                };
+                let before = ObjectState::of(mesh);
+                edit(mesh);
+                (ObjectState::of(mesh) != before).then_some(Command::SetObject { index, state: before })
+            }
+            KeyTarget::Camera => {
+                let before = scene.camera.clone();
+                edit(&mut scene.camera);
+                (scene.camera != before).then_some(Command::SetCamera(before))
+            }
+            KeyTarget::Light(index) => {
+                let Some(light) = scene.lights.get_mut(index) else {
+                    return;
+                };
+                let before = light.clone();
+                edit(light);
+                (*light != before).then_some(Command::SetLight { index, light: before })
+            }
         };
+
+        if let Some(undo) = undo {
+            self.history.record(undo);
+        }
     }
 
-    output
-}
+    // The parts of `Content` that get saved with the scene
+    fn view_state(&self) -> ViewState {
+        ViewState {
+            selected_object: self.selected_object,
+            rotation_index: self.rotation_index,
+            rotation_speed: [self.speed_slider.0, self.speed_slider.1, self.speed_slider.2],
+            shading: self.renderer.shading,
+            euler_order: self.euler_order,
+            camera_mode: self.camera_mode,
+            gizmo_mode: self.gizmo_mode,
+            gizmo_space: self.gizmo_space,
+            snapping: self.snapping,
+            animation_time: self.time,
+            looping: self.looping,
+        }
+    }
 
-impl eframe::App for Content {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut deltaTime = ctx.input(|ctx| ctx.stable_dt);
-        let stroke = Stroke::new(0.5, Color32::WHITE);
+    fn open_scene(&mut self, scene: Scene, view: ViewState) {
+        // The file can point at objects it doesn't have
+        let count = scene.objects.len();
+        self.current_scene = scene;
+        self.selected_object = view.selected_object.filter(|index| *index < count);
+        self.rotation_index = view.rotation_index.filter(|index| *index < count);
+        self.speed_slider = (view.rotation_speed[0], view.rotation_speed[1], view.rotation_speed[2]);
+        self.renderer.shading = view.shading;
+        self.euler_order = view.euler_order;
+        self.camera_mode = view.camera_mode;
+        self.gizmo_mode = view.gizmo_mode;
+        self.gizmo_space = view.gizmo_space;
+        self.snapping = view.snapping;
+        self.gizmo_drag = None;
+        self.time = view.animation_time;
+        self.looping = view.looping;
+        self.playing = false;
+        self.history.clear();
+        self.skip_edit_diff = true;
+    }
 
-        handle_input(&mut self.current_scene, ctx, deltaTime);
+    fn handle_shortcuts(&mut self, ctx: &Context) {
+        // Text fields have their own undo
+        if ctx.wants_keyboard_input() {
+            return;
+        }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            // -----------------------------
-            // * drag and drop handling *
-            if !self.dropped_files.is_empty() {
-                ui.group(|ui| {
-                    ui.label("Dropped files:");
-
-                    for file in &self.dropped_files {
-                        println!("{:#?}", file);
-                        let mut info = if let Some(path) = &file.path {
-                            path.display().to_string()
-                        } else if !file.name.is_empty() {
-                            file.name.clone()
-                        } else {
-                            "???".to_owned()
-                        };
+        let redo = ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z)));
+        let undo = ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Z)));
 
-                        if Option::is_some(&file.bytes) {
-                            self.current_scene.objects.append(&mut vec![drag_to_mesh(&file.bytes, [0.0,0.0,0.0], &info.as_str())]);
-                        }
+        if redo {
+            self.redo();
+        } else if undo {
+            self.undo();
+        }
+    }
 
-                        //loads from path
-                        if !cfg!(target_arch = "wasm32") {
-                            if let Some(path) = &file.path {
-                                match File::open(&path) {
-                                    Ok(file2) => {
-                                        let mut input = BufReader::new(file2);
-                                        // Now you can use 'input' for reading from the file.
-                                        let mesh: Obj = match load_obj(input) {
-                                            Ok(mesh) => mesh,
-                                            Err(err) => {
-                                                eprintln!("Error loading OBJ: {:?}", err);
-                                                return; 
-                                            }
-                                        };
-                        
-                                        let mut mesh_vertices = vec![];
-                                        let mut mesh_indices = vec![];
-                        
-                                        for index in &mesh.indices {
-                                            mesh_indices.push(*index as u32);
-                                        }
-                        
-                                        for vertex in &mesh.vertices {
-                                            let position = vertex.position;
-                                            let mesh_vertex = (position[0] as f32, position[1] as f32, position[2] as f32);
-                                            mesh_vertices.push(mesh_vertex);
-                                        }
-
-                                        let name: &str = mesh.name.as_deref().unwrap_or("Imported Object");
-
-                                        
-                                        let output = Mesh {
-                                            name: name.to_string(),
-                                            vertices: mesh_vertices,
-                                            indices: mesh_indices,
-                                            position: [0.0, 0.0, 0.0],
-                                            rotation: [0.0, 0.0, 0.0],
-                                        };
-                        
-                                        self.current_scene.objects.append(&mut vec![output]);
-                                    }
-                                    Err(err) => {
-                                        eprintln!("Error opening file: {:?}", err);
-                                        // Handle the error appropriately.
-                                    }
-                                }
-                            }
-                        }
-                        
+    fn undo(&mut self) {
+        if let Some(moves) = self.history.undo(&mut self.current_scene) {
+            self.after_history_step(&moves);
+        }
+    }
 
+    fn redo(&mut self) {
+        if let Some(moves) = self.history.redo(&mut self.current_scene) {
+            self.after_history_step(&moves);
+        }
+    }
 
-                        let mut additional_info = vec![];
-                        if !file.mime.is_empty() {
-                            additional_info.push(format!("type: {}", file.mime));
-                        }
-                        if let Some(bytes) = &file.bytes {
-                            additional_info.push(format!("{} bytes", bytes.len()));
-                        }
-                        if !additional_info.is_empty() {
-                            info += &format!(" ({})", additional_info.join(", "));
-                        }
+    // Objects coming back, like when undoing a delete, get selected again
+    fn after_history_step(&mut self, moves: &ObjectMoves) {
+        self.follow_objects(moves);
+        if let Some(index) = moves.first_inserted() {
+            self.selected_object = Some(index);
+        }
+        let count = self.current_scene.objects.len();
+        self.selected_object = self.selected_object.filter(|index| *index < count);
+        self.rotation_index = self.rotation_index.filter(|index| *index < count);
+        self.continuing_edit = false;
+        self.skip_edit_diff = true;
+    }
+
+    // Keeps the selection and the spinning object on the same objects when others come and go
+    fn follow_objects(&mut self, moves: &ObjectMoves) {
+        self.selected_object = self.selected_object.and_then(|index| moves.follow(index));
+        self.rotation_index = self.rotation_index.and_then(|index| moves.follow(index));
+    }
+
+    // Zeroes one of the angles the rotation is shown as
+    fn reset_rotation_axis(&mut self, index: usize, axis: usize) {
+        let transform = &mut self.current_scene.objects[index].transform;
+        let mut angles = transform.euler(self.euler_order);
+        angles[axis] = 0.0;
+        transform.set_euler(angles, self.euler_order);
+        self.euler_angles = None;
+    }
+
+    // Carries out an edit made on purpose, like a delete, and remembers how to take it back
+    fn perform(&mut self, command: Command) {
+        let moves = command.object_moves();
+        let undo = command.apply(&mut self.current_scene);
+        self.follow_objects(&moves);
+        self.history.record(undo);
+        self.skip_edit_diff = true;
+    }
+
+    // Attaches an object to another, or to nothing, without moving it in the world
+    fn reparent_command(&self, index: usize, parent: Option<usize>) -> Option<Command> {
+        let scene = &self.current_scene;
+        let parent_world = parent.map_or_else(mat4_identity, |parent| scene.world_matrix(parent));
+        let local = mat4_mul(&mat4_inverse(&parent_world)?, &scene.world_matrix(index));
+        let mut state = ObjectState::of(&scene.objects[index]);
+        state.parent = parent;
+        state.transform = Transform::from_matrix(&local);
+        Some(Command::SetObject { index, state })
+    }
+
+    fn reparent(&mut self, index: usize, parent: Option<usize>) {
+        if parent.is_some_and(|parent| parent == index || self.current_scene.is_descendant(parent, index)) {
+            self.log(LogLevel::Warning, "An object can't be attached to itself or one of its children".to_string());
+            return;
+        }
+        if self.current_scene.objects[index].parent == parent {
+            return;
+        }
+
+        if let Some(command) = self.reparent_command(index, parent) {
+            self.perform(command);
+        }
+    }
+
+    // Children move up to the deleted object's parent and stay where they are
+    fn delete_object(&mut self, index: usize) {
+        let parent = self.current_scene.objects[index].parent;
+        let mut commands: Vec<Command> = self
+            .current_scene
+            .children(index)
+            .filter_map(|child| self.reparent_command(child, parent))
+            .collect();
+        commands.push(Command::RemoveObjects { index, count: 1 });
+
+        self.perform(Command::Batch(commands));
+    }
+
+    fn edit_snapshot(&self) -> EditSnapshot {
+        let scene = &self.current_scene;
+        EditSnapshot {
+            camera: scene.camera.clone(),
+            lights: scene.lights.clone(),
+            object_count: scene.objects.len(),
+            object: self
+                .selected_object
+                .and_then(|index| scene.objects.get(index).map(|mesh| (index, ObjectState::of(mesh)))),
+        }
+    }
+
+    // Turns whatever the settings changed this frame into undo steps. Adding and removing things
+    // records its own commands, so only edits that kept the counts the same are looked at.
+    fn record_edits(&mut self, ctx: &Context, before: EditSnapshot) {
+        if std::mem::take(&mut self.skip_edit_diff) {
+            return;
+        }
+
+        let scene = &self.current_scene;
+        let mut commands = vec![];
+
+        if scene.camera != before.camera {
+            commands.push(Command::SetCamera(before.camera));
+        }
+
+        if scene.lights.len() == before.lights.len() {
+            for (index, (light, old)) in scene.lights.iter().zip(before.lights).enumerate() {
+                if *light != old {
+                    commands.push(Command::SetLight { index, light: old });
+                }
+            }
+        }
 
-                        ui.label(info);
-                         }
-                         self.dropped_files = vec!();
+        if scene.objects.len() == before.object_count {
+            if let Some((index, state)) = before.object {
+                if ObjectState::of(&scene.objects[index]) != state {
+                    commands.push(Command::SetObject { index, state });
+                }
+            }
+        }
+
+        // A drag or a focused text field keeps adding to the same step until it's let go
+        let editing = ctx.input(|i| i.pointer.any_down()) || ctx.wants_keyboard_input();
+        let changed = !commands.is_empty();
+
+        for command in commands {
+            if self.continuing_edit {
+                self.history.record_merged(command);
+            } else {
+                self.history.record(command);
+            }
+        }
+        self.continuing_edit = editing && (self.continuing_edit || changed);
+    }
+
+    fn log(&mut self, level: LogLevel, message: String) {
+        self.log.push(LogEntry { level, message });
+        if self.log.len() > MAX_LOG_ENTRIES {
+            self.log.remove(0);
+        }
+    }
+
+    fn handle_dropped_files(&mut self) {
+        for file in std::mem::take(&mut self.dropped_files) {
+            let name = if let Some(path) = &file.path {
+                path.display().to_string()
+            } else if !file.name.is_empty() {
+                file.name.clone()
+            } else {
+                "???".to_owned()
+            };
+
+            if is_image(&name) {
+                // Images become the texture of whatever is selected
+                self.load_dropped_texture(&name, &file);
+            } else if is_scene(&name) {
+                self.open_dropped_scene(&name, &file);
+            } else {
+                self.import_dropped_file(&name, &file);
+            }
+        }
+    }
+
+    fn import_dropped_file(&mut self, name: &str, file: &egui::DroppedFile) {
+        // The browser hands over the bytes, native builds get a path
+        let source = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => ImportSource::Bytes { name, bytes },
+            (None, Some(path)) => ImportSource::Path(path),
+            (None, None) => return,
+        };
+
+        let mut warnings = vec![];
+        let result = import_mesh_with_warnings(source, &mut warnings);
+
+        for warning in warnings {
+            self.log(LogLevel::Warning, format!("{}: {}", name, warning));
+        }
+
+        match result {
+            Ok(mut meshes) => {
+                let triangles: usize = meshes.iter().map(|mesh| mesh.indices.len() / 3).sum();
+                self.log(LogLevel::Info, format!("Imported {} ({} objects, {} triangles)", name, meshes.len(), triangles));
+                self.history.record(Command::RemoveObjects {
+                    index: self.current_scene.objects.len(),
+                    count: meshes.len(),
                 });
-                    
+                self.current_scene.objects.append(&mut meshes);
             }
+            Err(err) => self.log(LogLevel::Error, format!("Couldn't import {}: {}", name, err)),
+        }
+    }
 
-            preview_files_being_dropped(ctx);
+    fn open_dropped_scene(&mut self, name: &str, file: &egui::DroppedFile) {
+        let result = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => scene_from_bytes(bytes),
+            (None, Some(path)) => load_scene(path),
+            (None, None) => return,
+        };
 
-            ctx.input(|i| {
-                if !i.raw.dropped_files.is_empty() {
-                    self.dropped_files = i.raw.dropped_files.clone();
+        match result {
+            Ok((scene, view)) => {
+                self.open_scene(scene, view);
+                self.log(LogLevel::Info, format!("Opened {}", name));
+            }
+            Err(err) => self.log(LogLevel::Error, format!("Couldn't open {}: {}", name, err)),
+        }
+    }
+
+    fn load_dropped_texture(&mut self, name: &str, file: &egui::DroppedFile) {
+        let Some(index) = self.selected_object.filter(|index| *index < self.current_scene.objects.len()) else {
+            self.log(LogLevel::Warning, format!("Select an object before dropping {} on it", name));
+            return;
+        };
+
+        let texture = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => Texture::from_bytes(bytes),
+            (None, Some(path)) => std::fs::read(path)
+                .map_err(|err| format!("Error opening file: {}", err))
+                .and_then(|bytes| Texture::from_bytes(&bytes)),
+            (None, None) => return,
+        };
+
+        match texture {
+            Ok(texture) => {
+                let mesh = &mut self.current_scene.objects[index];
+                self.history.record(Command::SetObject { index, state: ObjectState::of(mesh) });
+                mesh.material.texture = Some(Arc::new(texture));
+            }
+            Err(err) => self.log(LogLevel::Error, format!("Couldn't load {}: {}", name, err)),
+        }
+    }
+}
+
+// Import problems and other things the user should know about, shown in the corner until cleared
+fn log_window(ctx: &Context, log: &mut Vec<LogEntry>) {
+    if log.is_empty() {
+        return;
+    }
+
+    egui::Window::new("Log")
+        .anchor(Align2::RIGHT_BOTTOM, vec2(-8.0, -8.0))
+        .resizable(false)
+        .collapsible(true)
+        .show(ctx, |ui| {
+            ui.set_max_width(320.0);
+            ScrollArea::vertical().max_height(160.0).stick_to_bottom(true).show(ui, |ui| {
+                for entry in log.iter() {
+                    let color = match entry.level {
+                        LogLevel::Info => ui.visuals().text_color(),
+                        LogLevel::Warning => ui.visuals().warn_fg_color,
+                        LogLevel::Error => ui.visuals().error_fg_color,
+                    };
+                    ui.colored_label(color, &entry.message);
                 }
             });
+            if ui.button("Clear").clicked() {
+                log.clear();
+            }
+        });
+}
 
-            // * Drag and drop ^
-            // * SUPER bad and doesnt handle errors but i'm pretty sure it won't crash
-            // -----------------------
+// The ray through a point on the viewport, which fills `screen`
+fn viewport_ray(camera: &Camera, pos: Pos2, screen: Rect) -> Ray {
+    screen_ray(camera, pos.x - screen.min.x, pos.y - screen.min.y, screen.width(), screen.height())
+}
 
-            render_scene(&self.current_scene, stroke, &ui);
+// Shortest distance from `pos` to a line through `points`, around to the first one again when closed
+fn distance_to_polyline(pos: Pos2, points: &[Pos2], closed: bool) -> f32 {
+    let count = if closed { points.len() } else { points.len().saturating_sub(1) };
+    (0..count)
+        .map(|i| {
+            let (start, end) = (points[i], points[(i + 1) % points.len()]);
+            let along = end - start;
+            let t = if along.length_sq() > 0.0 {
+                ((pos - start).dot(along) / along.length_sq()).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            pos.distance(start + along * t)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
 
+fn timeline_window(ctx: &Context, reference: &mut Content) {
+    egui::Window::new("Timeline")
+        .anchor(Align2::CENTER_BOTTOM, vec2(0.0, -8.0))
+        .resizable(false)
+        .collapsible(true)
+        .show(ctx, |ui| {
+            ui.set_width(440.0);
+
+            ui.horizontal(|ui| {
+                if ui.button(if reference.playing { "Pause" } else { "Play" }).clicked() {
+                    // Playing from the very end starts over
+                    if !reference.playing && reference.time >= reference.current_scene.animation_length {
+                        reference.time = 0.0;
+                    }
+                    reference.playing = !reference.playing;
+                }
+                if ui.button("Stop").clicked() {
+                    reference.playing = false;
+                    reference.time = 0.0;
+                    reference.time_moved = true;
+                }
+                ui.checkbox(&mut reference.looping, "Loop");
 
-            Frame::popup(ui.style())
-            .stroke(Stroke::NONE)
-            .show(ui, |ui| {
-                ui.set_max_width(170.0);
-                CollapsingHeader::new("Settings")
-                .show(ui, |ui| settings_menu(ui, self, deltaTime, _frame))
+                let length = reference.current_scene.animation_length;
+                if ui.add(egui::DragValue::new(&mut reference.time).speed(0.01).clamp_range(0.0..=length).suffix(" s")).changed() {
+                    reference.time_moved = true;
+                }
+                ui.label("of");
+                ui.add(egui::DragValue::new(&mut reference.current_scene.animation_length).speed(0.1).clamp_range(0.1..=3600.0).suffix(" s"));
             });
 
-            if let Some(index) = self.rotation_index {
-                if let Some(object) = self.current_scene.objects.get_mut(index) {
-                    object.rotation[0] += self.speed_slider.0 * 10.0 * deltaTime;
-                    object.rotation[1] += self.speed_slider.1 * 10.0 * deltaTime;
-                    object.rotation[2] += self.speed_slider.2 * 10.0 * deltaTime;
-            
-                    /*
-                    * Limit rotation amount without changing effective rotation
-                    object.rotation[0] = object.rotation[0].rem_euclid(360.0);
-                    object.rotation[1] = object.rotation[1].rem_euclid(360.0);
-                    object.rotation[2] = object.rotation[2].rem_euclid(360.0);
-                    * Commented because stuttering issues???
-                     */
-                }
+            let keys = reference.key_target().map(|target| target.key_times()).unwrap_or_default();
+            let length = reference.current_scene.animation_length;
+            if timeline_strip(ui, &mut reference.time, length, &keys) {
+                reference.time_moved = true;
             }
-            
+
+            ui.horizontal(|ui| {
+                let target_name = |target: KeyTarget, scene: &Scene, selected: Option<usize>| match target {
+                    KeyTarget::SelectedObject => match selected.and_then(|index| scene.objects.get(index)) {
+                        Some(mesh) => mesh.name.clone(),
+                        None => "Selected object".to_string(),
+                    },
+                    KeyTarget::Camera => "Camera".to_string(),
+                    KeyTarget::Light(index) => format!("Light {}", index + 1),
+                };
+
+                let scene = &reference.current_scene;
+                let selected = reference.selected_object;
+                let mut target = reference.key_target;
+                ComboBox::from_id_source("key_target")
+                    .selected_text(target_name(target, scene, selected))
+                    .width(120.0)
+                    .show_ui(ui, |ui| {
+                        let targets = [KeyTarget::SelectedObject, KeyTarget::Camera].into_iter().chain((0..scene.lights.len()).map(KeyTarget::Light));
+                        for option in targets {
+                            ui.selectable_value(&mut target, option, target_name(option, scene, selected));
+                        }
+                    });
+                reference.key_target = target;
+
+                let time = reference.time;
+                let Some(current) = reference.key_target().map(|target| *target.interpolation_mut()) else {
+                    ui.label("Nothing to key");
+                    return;
+                };
+
+                if ui.button("Key").on_hover_text("Keys everything about it as it is now").clicked() {
+                    reference.edit_keys(|target| target.set_key(time));
+                }
+                if ui.add_enabled(keys.iter().any(|key| (key - time).abs() < KEY_TOLERANCE), Button::new("Delete key")).clicked() {
+                    reference.edit_keys(|target| {
+                        target.remove_key(time);
+                    });
+                }
+                if ui.add_enabled(!keys.is_empty(), Button::new("Clear")).clicked() {
+                    reference.edit_keys(|target| target.clear_keys());
+                }
+
+                let mut interpolation = current;
+                ComboBox::from_id_source("interpolation")
+                    .selected_text(format!("{:?}", interpolation))
+                    .width(70.0)
+                    .show_ui(ui, |ui| {
+                        for option in [Interpolation::Step, Interpolation::Linear, Interpolation::Cubic] {
+                            ui.selectable_value(&mut interpolation, option, format!("{:?}", option));
+                        }
+                    });
+                if interpolation != current {
+                    reference.edit_keys(|target| *target.interpolation_mut() = interpolation);
+                    reference.time_moved = true;
+                }
+            });
         });
-        ctx.request_repaint();
+}
+
+// The strip under the timeline buttons: ticks along the time, a diamond at every keyframe of
+// what is being keyed and a line where the time is. Clicking or dragging on it moves the time.
+fn timeline_strip(ui: &mut Ui, time: &mut f32, length: f32, keys: &[f32]) -> bool {
+    let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), 28.0), Sense::click_and_drag());
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+    let x_at = |time: f32| rect.left() + time / length * rect.width();
+
+    // Ticks at round numbers of seconds, far enough apart to tell them apart
+    let step = [0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0, 300.0]
+        .into_iter()
+        .find(|step| step / length * rect.width() >= 12.0)
+        .unwrap_or(length);
+    let tick_color = visuals.weak_text_color();
+    for tick in 0..=(length / step) as usize {
+        let x = x_at(tick as f32 * step);
+        painter.line_segment([pos2(x, rect.bottom() - 5.0), pos2(x, rect.bottom())], Stroke::new(1.0, tick_color));
+    }
+
+    for key in keys {
+        let center = pos2(x_at(*key), rect.center().y);
+        let diamond = vec![center + vec2(0.0, -5.0), center + vec2(5.0, 0.0), center + vec2(0.0, 5.0), center + vec2(-5.0, 0.0)];
+        painter.add(Shape::convex_polygon(diamond, Color32::from_rgb(255, 200, 60), Stroke::NONE));
+    }
+
+    let x = x_at(*time);
+    painter.line_segment([pos2(x, rect.top()), pos2(x, rect.bottom())], Stroke::new(2.0, visuals.strong_text_color()));
+
+    match response.interact_pointer_pos().filter(|_| response.dragged() || response.clicked()) {
+        Some(pos) => {
+            *time = ((pos.x - rect.left()) / rect.width() * length).clamp(0.0, length);
+            true
+        }
+        None => false,
     }
 }
 
+// Scenes are saved as JSON, glTF files are JSON too but have their own extension
+fn is_scene(name: &str) -> bool {
+    name.to_lowercase().ends_with(".json")
+}
+
+fn is_image(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.ends_with(".png") || name.ends_with(".jpg") || name.ends_with(".jpeg")
+}
+
 fn preview_files_being_dropped(ctx: &egui::Context) {
     use egui::*;
     use std::fmt::Write as _;
@@ -339,352 +1173,20 @@ fn preview_files_being_dropped(ctx: &egui::Context) {
     }
 }
 
-fn handle_input(reference : &mut Scene, ctx : &Context, deltaTime: f32) {
-    let camera_rotation = reference.camera_rotation[1];
-    let move_speed = 10.0 * deltaTime;
-    //Yes, this is a mess.
-    //No, I don't care.
-    //Calculates direction based on camera Y rotation.
-
-    if ctx.input(|i| i.key_down(Key::W)) {
-        reference.camera_position[0] -= camera_rotation.to_radians().sin() * move_speed;
-        reference.camera_position[2] += camera_rotation.to_radians().cos() * move_speed;
-    }
-    if ctx.input(|i| i.key_down(Key::S)) {
-        reference.camera_position[0] += camera_rotation.to_radians().sin() * move_speed;
-        reference.camera_position[2] -= camera_rotation.to_radians().cos() * move_speed;
-    }
-    if ctx.input(|i| i.key_down(Key::A)) {
-        reference.camera_position[0] += (camera_rotation.to_radians() + std::f32::consts::FRAC_PI_2).sin() * move_speed;
-        reference.camera_position[2] -= (camera_rotation.to_radians() + std::f32::consts::FRAC_PI_2).cos() * move_speed;
-    }
-    if ctx.input(|i| i.key_down(Key::D)) {
-        reference.camera_position[0] -= (camera_rotation.to_radians() + std::f32::consts::FRAC_PI_2).sin() * move_speed;
-        reference.camera_position[2] += (camera_rotation.to_radians() + std::f32::consts::FRAC_PI_2).cos() * move_speed;
-    }
-    if ctx.input(|i| i.key_down(Key::ArrowLeft)) {
-        reference.camera_rotation[1] -= (7000.0 * deltaTime).to_radians();
-    }
-    if ctx.input(|i| i.key_down(Key::ArrowRight)) {
-        reference.camera_rotation[1] += (7000.0 * deltaTime).to_radians();
-    }
-}
-
-fn apply_rotation(vertex: (f32, f32, f32), angles: [f32; 3]) -> [f32; 3] {
-    let sin_x = f32::sin(angles[0]);
-    let cos_x = f32::cos(angles[0]);
-    let sin_y = f32::sin(angles[1]);
-    let cos_y = f32::cos(angles[1]);
-    let sin_z = f32::sin(angles[2]);
-    let cos_z = f32::cos(angles[2]);
-
-    let mut result = [0.0, 0.0, 0.0];
-
-    // X-axis
-    result[0] = vertex.0;
-    result[1] = cos_x * vertex.1 - sin_x * vertex.2;
-    result[2] = sin_x * vertex.1 + cos_x * vertex.2;
-
-    // Y-axis
-    let temp_x = cos_y * result[0] + sin_y * result[2];
-    result[2] = -sin_y * result[0] + cos_y * result[2];
-    result[0] = temp_x;
-
-    // Z-axis
-    let temp_x = cos_z * result[0] - sin_z * result[1];
-    result[1] = sin_z * result[0] + cos_z * result[1];
-    result[0] = temp_x;
-
-    result
-}
-
-
-fn render_scene(scene: &Scene, stroke: Stroke, ui: &Ui) {
-    let canvas_width = ui.ctx().screen_rect().width();
-    let canvas_height = ui.ctx().screen_rect().height();
-    let half_width = canvas_width / 2.0;
-    let half_height = canvas_height / 2.0;
-
-
-    let mut mesh = egui::Mesh::default();
-    let mut triangles_with_depth: Vec<(usize, [Pos2; 3], f32, Color32)> = Vec::new();
-
-    for (object_index, mesh) in scene.objects.iter().enumerate() {
-        let vertices = &mesh.vertices;
-        let indices = &mesh.indices;
-        let rotation = &[
-            mesh.rotation[0].to_radians(),
-            mesh.rotation[1].to_radians(),
-            mesh.rotation[2].to_radians(),
-        ];
-        let position = &mesh.position;
-
-        for i in (0..indices.len()).step_by(3) {
-            let a = indices[i] as usize;
-            let b = indices[i + 1] as usize;
-            let c = indices[i + 2] as usize;
-            let vertex_a = &vertices[a];
-            let vertex_b = &vertices[b];
-            let vertex_c = &vertices[c];
-
-            let rotated_a = apply_rotation(*vertex_a, *rotation);
-            let rotated_b = apply_rotation(*vertex_b, *rotation);
-            let rotated_c = apply_rotation(*vertex_c, *rotation);
-
-            let pose_a = [
-                rotated_a[0] + position[0],
-                rotated_a[1] + position[1],
-                rotated_a[2] + position[2],
-            ];
-
-            let pose_b = [
-                rotated_b[0] + position[0],
-                rotated_b[1] + position[1],
-                rotated_b[2] + position[2],
-            ];
-
-            let pose_c = [
-                rotated_c[0] + position[0],
-                rotated_c[1] + position[1],
-                rotated_c[2] + position[2],
-            ];
-
-            //Lighting is calculated here, as everything after takes the camera into account. Lighting should not be camera dependent.
-            let lighting_a = calculate_lighting(pose_a, pose_b, pose_c, scene.light.position, scene.light.intensity , 5000.0);
-
-            let posed_a = [
-                pose_a[0] + scene.camera_position[0],
-                pose_a[1] + scene.camera_position[1],
-                pose_a[2] + scene.camera_position[2] - 10.0,
-            ];
-
-            let posed_b = [
-                pose_b[0] + scene.camera_position[0],
-                pose_b[1] + scene.camera_position[1],
-                pose_b[2] + scene.camera_position[2] - 10.0,
-            ];
-
-            let posed_c = [
-                pose_c[0] + scene.camera_position[0],
-                pose_c[1] + scene.camera_position[1],
-                pose_c[2] + scene.camera_position[2] - 10.0,
-            ];
-
-            let mut final_a = apply_rotation(
-                (posed_a[0], posed_a[1], posed_a[2]),
-                [
-                    scene.camera_rotation[0].to_radians(),
-                    scene.camera_rotation[1].to_radians(),
-                    scene.camera_rotation[2].to_radians(),
-                ],
-            );
-
-            let mut final_b = apply_rotation(
-                (posed_b[0], posed_b[1], posed_b[2]),
-                [
-                    scene.camera_rotation[0].to_radians(),
-                    scene.camera_rotation[1].to_radians(),
-                    scene.camera_rotation[2].to_radians(),
-                ],
-            );
-
-            let mut final_c = apply_rotation(
-                (posed_c[0], posed_c[1], posed_c[2]),
-                [
-                    scene.camera_rotation[0].to_radians(),
-                    scene.camera_rotation[1].to_radians(),
-                    scene.camera_rotation[2].to_radians(),
-                ],
-            );
-
-            final_a = [
-                final_a[0],
-                final_a[1],
-                final_a[2] + 10.0,
-            ];
-            final_b = [
-                final_b[0],
-                final_b[1],
-                final_b[2] + 10.0,
-            ];
-            final_c = [
-                final_c[0],
-                final_c[1],
-                final_c[2] + 10.0,
-            ];
-
-            let depth = -(final_a[2] + final_b[2] + final_c[2]) / 3.0;
-
-            let depth_a = final_a[2] * -0.1;
-            let depth_b = final_b[2] * -0.1;
-            let depth_c = final_c[2] * -0.1;
-
-            if depth_a > -1.0 && depth_b > -1.0 && depth_c > -1.0 {
-                let perspective_factor_a = 1.0 / (1.0 + depth_a);
-                let perspective_factor_b = 1.0 / (1.0 + depth_b);
-                let perspective_factor_c = 1.0 / (1.0 + depth_c);
-
-                let line_start_a = [
-                    final_a[0] * perspective_factor_a * 100.0 + half_width,
-                    canvas_height - final_a[1] * perspective_factor_a * 100.0 - half_height,
-                ];
-
-                let line_end_b = [
-                    final_b[0] * perspective_factor_b * 100.0 + half_width,
-                    canvas_height - final_b[1] * perspective_factor_b * 100.0 - half_height,
-                ];
-
-                let line_end_c = [
-                    final_c[0] * perspective_factor_c * 100.0 + half_width,
-                    canvas_height - final_c[1] * perspective_factor_c * 100.0 - half_height,
-                ];
-
-                let triangle = [
-                    Pos2::new(line_start_a[0], line_start_a[1]),
-                    Pos2::new(line_end_b[0], line_end_b[1]),
-                    Pos2::new(line_end_c[0], line_end_c[1]),
-                ];
-
-                let lighting = value_to_color((lighting_a[0] + lighting_a[1] + lighting_a[2]) / 3.0, 0.0, 1.0 );
-                triangles_with_depth.push((object_index, triangle, depth, lighting));
-            }
-        }
-    }
-
- triangles_with_depth.sort_by(|a, b| {
-    b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.0.cmp(&b.0))
-});
-    for (_, triangle, _, lighting) in triangles_with_depth {
-        let color = lighting;
-
-        mesh.colored_vertex(triangle[0], color);
-        mesh.colored_vertex(triangle[1], color);
-        mesh.colored_vertex(triangle[2], color);
-
-        let vertex_count = mesh.vertices.len() as u32;
-        mesh.add_triangle(vertex_count - 3, vertex_count - 2, vertex_count - 1);
-    }
-
-    ui.painter().add(egui::Shape::mesh(mesh));
-}
-
-fn calculate_lighting(
-    vertex_a: [f32; 3],
-    vertex_b: [f32; 3],
-    vertex_c: [f32; 3],
-    light_position: [f32; 3],
-    intensity: f32,
-    max_distance: f32,
-) -> [f32; 3] {
-    let mut normal = calculate_normal(vertex_a, vertex_b, vertex_c);
-    normal = [
-        normal[0],
-        normal[1],
-        normal[2],
-        ];
-
-    // Calculate the vector from the triangle vertices to the light source
-    let to_light = [
-        light_position[0] - vertex_a[0],
-        light_position[1] - vertex_a[1],
-        light_position[2] - vertex_a[2],
-    ];
-
-    // Calculate the distance from the light source to the triangle
-    let distance = f32::sqrt(to_light[0] * to_light[0] + to_light[1] * to_light[1] + to_light[2] * to_light[2]);
-
-    if distance > max_distance {
-        // Light is too far away, no lighting
-        return [0.0, 0.0, 0.0];
-    }
-
-    // Normalize the to_light vector
-    let to_light_length = f32::sqrt(to_light[0] * to_light[0] + to_light[1] * to_light[1] + to_light[2] * to_light[2]);
-    let to_light_normalized = [
-        to_light[0] / to_light_length,
-        to_light[1] / to_light_length,
-        to_light[2] / to_light_length,
-    ];
-
-    // Calculate the cosine of the angle between the normal and the to_light vector
-    let cos_theta = normal[0] * to_light_normalized[0] + normal[1] * to_light_normalized[1] + normal[2] * to_light_normalized[2];
-
-    if cos_theta <= 0.0 {
-        // Light is behind the triangle, no lighting
-        return [0.0, 0.0, 0.0];
-    }
-
-    //Lambert's Cosine Law
-    let lighting_intensity = intensity * cos_theta / (distance * distance);
-
-    [lighting_intensity, lighting_intensity, lighting_intensity]
-}
-
-fn calculate_normal(vertex_a: [f32; 3], vertex_b: [f32; 3], vertex_c: [f32; 3]) -> [f32; 3] {
-    // Calculate the cross product of two edges of the triangle to find the normal vector
-    let edge1 = [
-        vertex_b[0] - vertex_a[0],
-        vertex_b[1] - vertex_a[1],
-        vertex_b[2] - vertex_a[2],
-    ];
-    let edge2 = [
-        vertex_c[0] - vertex_a[0],
-        vertex_c[1] - vertex_a[1],
-        vertex_c[2] - vertex_a[2],
-    ];
-
-    let normal = [
-        edge1[1] * edge2[2] - edge1[2] * edge2[1],
-        edge1[2] * edge2[0] - edge1[0] * edge2[2],
-        edge1[0] * edge2[1] - edge1[1] * edge2[0],
-    ];
-
-    // Normalize the normal vector
-    let normal_length = f32::sqrt(normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]);
-    [
-        normal[0] / normal_length,
-        normal[1] / normal_length,
-        normal[2] / normal_length,
-    ]
-}
-
-
-
-
-
-fn value_to_color(value: f32, min_value: f32, max_value: f32) -> Color32 {
-
-    let clamped_value = value.clamp(min_value, max_value);
-
-    // Map the clamped value to the range [0.0, 1.0]
-    let interpolation_factor = (clamped_value - min_value) / (max_value - min_value);
-
-    // Calculate the color components
-    //Change the 20.0 to change the base color
-    let red 
-    = (20.0 + (interpolation_factor * 255.0)) as u8;
-    let green
-    = (20.0 + (interpolation_factor * 255.0)) as u8;
-    let blue
-    = (20.0 + (interpolation_factor * 255.0)) as u8;
-
-    Color32::from_rgb(red, green, blue)
-}
-
 /*
  * --------------------------------------------
  *                    UI
 */
 
-fn settings_menu(ui: &mut Ui, reference : &mut Content, deltaTime: f32, frame: &mut eframe::Frame) {
+fn settings_menu(ui: &mut Ui, reference : &mut Content, frame: &mut eframe::Frame) {
     egui::ScrollArea::vertical().show(ui, |ui| {
-    scene_view(ui, reference, deltaTime);
+    scene_view(ui, reference);
 
         ui.add_space(10.0);
         ui.separator();
     
-    if reference.selected_object != None {
-        transform_ui(ui, reference, deltaTime);
+    if reference.selected_object.is_some() {
+        transform_ui(ui, reference);
 
         ui.add_space(10.0);
         ui.separator();
@@ -692,50 +1194,133 @@ fn settings_menu(ui: &mut Ui, reference : &mut Content, deltaTime: f32, frame: &
 
 
 
-    gerneral_settings(ui, reference, deltaTime);
+    gerneral_settings(ui, reference);
+
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(4.0);
+
+    camera_settings(ui, reference);
 
         ui.add_space(10.0);
         ui.separator();
         ui.add_space(4.0);
 
-    camera_settings(ui, reference, deltaTime);
+    export_ui(ui, reference);
 
     ui.add_space(4.0);
 
+    let stats = reference.renderer.stats();
+    ui.label(format!("Triangles: {} ({} culled)", stats.triangles, stats.culled));
     ui.label(format!("CPU time: {:.1} ms", 1e3 * frame.info().cpu_usage.unwrap_or(0.0)))
     });
 }
 
-fn scene_view(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
-    ui.add(TextEdit::singleline(&mut "Scene:").desired_width(110.0)); 
+fn file_menu(ui: &mut Ui, reference : &mut Content) {
+    ui.horizontal(|ui| {
+    ui.menu_button("File", |ui| {
+        ui.add(TextEdit::singleline(&mut reference.scene_path).desired_width(150.0))
+            .on_hover_text("Scene file, ends in .json");
+
+        ui.horizontal(|ui| {
+            let path = reference.scene_path.clone();
 
-    for (index, mesh) in reference.current_scene.objects.iter_mut().enumerate() {
+            if ui.button("Save").clicked() {
+                match save_scene(Path::new(&path), &reference.current_scene, &reference.view_state()) {
+                    Ok(()) => reference.log(LogLevel::Info, format!("Saved the scene to {}", path)),
+                    Err(err) => reference.log(LogLevel::Error, format!("Couldn't save {}: {}", path, err)),
+                }
+                ui.close_menu();
+            }
+
+            if ui.button("Open").clicked() {
+                match load_scene(Path::new(&path)) {
+                    Ok((scene, view)) => {
+                        reference.open_scene(scene, view);
+                        reference.log(LogLevel::Info, format!("Opened {}", path));
+                    }
+                    Err(err) => reference.log(LogLevel::Error, format!("Couldn't open {}: {}", path, err)),
+                }
+                ui.close_menu();
+            }
+        });
+    });
+
+    ui.menu_button("Edit", |ui| {
+        let undo = ui.add_enabled(reference.history.can_undo(), Button::new("Undo").shortcut_text("Ctrl+Z"));
+        if undo.clicked() {
+            reference.undo();
+        }
+        let redo = ui.add_enabled(reference.history.can_redo(), Button::new("Redo").shortcut_text("Ctrl+Shift+Z"));
+        if redo.clicked() {
+            reference.redo();
+        }
+    });
+    });
+}
+
+fn scene_view(ui: &mut Ui, reference : &mut Content) {
+    let header = ui.add(TextEdit::singleline(&mut "Scene:").desired_width(110.0)); 
+
+    // Where a dragged object can be dropped, the header stands for the scene itself
+    let mut drop_targets = vec![(header.rect, None)];
+
+    for index in reference.current_scene.tree_order() {
         let mut enabled = false;
         if Some(index) == reference.selected_object {
             enabled = true
         }
+        let depth = reference.current_scene.depth(index).unwrap_or(0);
+        let name = reference.current_scene.objects[index].name.to_string();
+
         ui.horizontal(|ui| {
-        ui.add(TextEdit::singleline(&mut "        ").desired_width(8.0));
-        if ui.toggle_value(&mut enabled, format!("{}", mesh.name)).clicked() {
+        ui.add(TextEdit::singleline(&mut "        ").desired_width(8.0 + 12.0 * depth as f32));
+        let response = ui.toggle_value(&mut enabled, name)
+            .interact(Sense::drag())
+            .on_hover_text("Drag onto another object to attach it, or onto Scene: to detach it");
+        if response.clicked() {
             reference.selected_object = Some(index);
             enabled = true
         }
+        if response.drag_started() {
+            reference.dragged_object = Some(index);
+        }
+        drop_targets.push((response.rect, Some(index)));
     });
     }
-    ui.add(TextEdit::singleline(&mut "To import an OBJ file,").desired_width(130.0)); 
+
+    if let Some(dragged) = reference.dragged_object {
+        let pointer = ui.ctx().pointer_interact_pos();
+        if let Some(name) = reference.current_scene.objects.get(dragged).map(|mesh| mesh.name.clone()) {
+            show_tooltip_at_pointer(ui.ctx(), Id::new("dragged_object"), |ui| ui.label(name));
+        }
+
+        if ui.input(|i| i.pointer.any_released()) {
+            reference.dragged_object = None;
+            let target = pointer.and_then(|pointer| drop_targets.iter().find(|(rect, _)| rect.contains(pointer)));
+            if let Some((_, parent)) = target {
+                if *parent != Some(dragged) && dragged < reference.current_scene.objects.len() {
+                    reference.reparent(dragged, *parent);
+                }
+            }
+        }
+    }
+    ui.add(TextEdit::singleline(&mut "To import a model,").desired_width(130.0)); 
     ui.add(TextEdit::singleline(&mut "just drag and drop it").desired_width(130.0)); 
     ui.add(TextEdit::singleline(&mut "onto the window.").desired_width(130.0)); 
-
-    ui.add(TextEdit::singleline(&mut "Only triangulated").desired_width(130.0)); 
-    ui.add(TextEdit::singleline(&mut "meshes are").desired_width(130.0)); 
-    ui.add(TextEdit::singleline(&mut "supported.").desired_width(130.0)); 
 }
 
-fn transform_ui(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
+fn transform_ui(ui: &mut Ui, reference : &mut Content) {
     ui.set_min_width(0.0);
 
         if let Some(selected_object) = reference.selected_object {
         ui.add(egui::TextEdit::singleline(&mut reference.current_scene.objects[selected_object].name));
+
+        let mesh = &reference.current_scene.objects[selected_object];
+        ui.label(format!("Faces: {} ({} triangles)", mesh.face_count, mesh.indices.len() / 3));
+        if let Some(parent) = mesh.parent.and_then(|parent| reference.current_scene.objects.get(parent)) {
+            ui.label(format!("Attached to {}", parent.name));
+        }
         }
     
         ui.add_space(4.0);
@@ -746,30 +1331,97 @@ fn transform_ui(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
         ui.horizontal(|ui| {
             if let Some(selected_object) = reference.selected_object {
                 if selected_object < reference.current_scene.objects.len() {
-                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].position[0]).speed(0.05));  
-                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].position[1]).speed(0.05));  
-                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].position[2]).speed(0.05));
+                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].transform.translation[0]).speed(0.05));  
+                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].transform.translation[1]).speed(0.05));  
+                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].transform.translation[2]).speed(0.05));
                 }
             } 
             
         });
+
+        ui.add(TextEdit::singleline(&mut "Scale:").desired_width(110.0));
+        ui.horizontal(|ui| {
+            if let Some(object) = reference.selected_object.and_then(|index| reference.current_scene.objects.get_mut(index)) {
+                for scale in object.transform.scale.iter_mut() {
+                    ui.add(egui::DragValue::new(scale).speed(0.01));
+                }
+            }
+        });
+
+        ui.add_space(4.0);
+        ui.add(TextEdit::singleline(&mut "Gizmo:").desired_width(110.0));
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut reference.gizmo_mode, GizmoMode::Translate, "Move");
+            ui.selectable_value(&mut reference.gizmo_mode, GizmoMode::Rotate, "Rotate");
+            ui.selectable_value(&mut reference.gizmo_mode, GizmoMode::Scale, "Scale");
+        });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut reference.gizmo_space, GizmoSpace::World, "World");
+            ui.selectable_value(&mut reference.gizmo_space, GizmoSpace::Local, "Local");
+        }).response.on_hover_text("Which way the handles point. Scale handles always follow the object.");
+        ui.checkbox(&mut reference.snapping.enabled, "Snap")
+            .on_hover_text("Holding Ctrl while dragging does the opposite");
+        if reference.snapping.enabled {
+            let snapping = &mut reference.snapping;
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut snapping.translation).speed(0.01).clamp_range(0.01..=100.0));
+                ui.add(egui::DragValue::new(&mut snapping.angle).speed(0.5).clamp_range(1.0..=180.0).suffix("°"));
+                ui.add(egui::DragValue::new(&mut snapping.scale).speed(0.01).clamp_range(0.01..=10.0));
+            });
+        }
     
         ui.add_space(4.0);
+
+        if let Some(object) = reference.selected_object.and_then(|index| reference.current_scene.objects.get_mut(index)) {
+            ui.add(TextEdit::singleline(&mut "Back-face Culling:").desired_width(110.0));
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut object.cull_mode, CullMode::Off, "Off");
+                ui.selectable_value(&mut object.cull_mode, CullMode::Clockwise, "CW");
+                ui.selectable_value(&mut object.cull_mode, CullMode::CounterClockwise, "CCW");
+            });
+            ui.add_space(4.0);
+
+            CollapsingHeader::new("Material")
+            .show(ui, |ui| material_ui(ui, &mut object.material));
+            ui.add_space(4.0);
+        }
         
-        rotation_ui(ui, reference, deltaTime);
+        rotation_ui(ui, reference);
 }
 
-fn rotation_ui(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
+fn rotation_ui(ui: &mut Ui, reference : &mut Content) {
     ui.vertical(|ui| {
-        ui.add(TextEdit::singleline(&mut "Rotation:").desired_width(110.0));
-
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut "Rotation:").desired_width(60.0));
+            let order = reference.euler_order;
+            ComboBox::from_id_source("euler_order")
+                .selected_text(order.name())
+                .width(50.0)
+                .show_ui(ui, |ui| {
+                    for option in EulerOrder::ALL {
+                        ui.selectable_value(&mut reference.euler_order, option, option.name());
+                    }
+                });
+        });
 
         ui.horizontal(|ui| {
             if let Some(selected_object) = reference.selected_object {
                 if selected_object < reference.current_scene.objects.len() {
-                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].rotation[0]).speed(0.1));  
-                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].rotation[1]).speed(0.1));  
-                    ui.add(egui::DragValue::new(&mut reference.current_scene.objects[selected_object].rotation[2]).speed(0.1));
+                    let order = reference.euler_order;
+                    let transform = &mut reference.current_scene.objects[selected_object].transform;
+                    let mut angles = match reference.euler_angles {
+                        Some((index, rotation, angles)) if index == selected_object && rotation == transform.rotation => angles,
+                        _ => transform.euler(order),
+                    };
+
+                    let mut changed = false;
+                    for angle in angles.iter_mut() {
+                        changed |= ui.add(egui::DragValue::new(angle).speed(0.1)).changed();
+                    }
+                    if changed {
+                        transform.set_euler(angles, order);
+                    }
+                    reference.euler_angles = Some((selected_object, transform.rotation, angles));
                 } 
             }
             
@@ -777,11 +1429,7 @@ fn rotation_ui(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
 
          if ui.button("Delete").clicked() {
             if let Some(selected_object) = reference.selected_object {
-                    reference.current_scene.objects.remove(selected_object);
-                    if Some(reference.selected_object) == Some(reference.rotation_index) {
-                        reference.rotation_index = None;
-                    }
-                    reference.selected_object = None;
+                    reference.delete_object(selected_object);
             }
     }
     });
@@ -794,11 +1442,9 @@ fn rotation_ui(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
         ui.add(egui::Slider::new(&mut reference.speed_slider.0, 0.0..=100.0));
 
         if let Some(selected_object) = reference.selected_object {
-            if selected_object < reference.current_scene.objects.len() {
-                if ui.button("Reset").clicked() {
-                    reference.current_scene.objects[selected_object].rotation[0] = 0.0;
-                    reference.speed_slider.0 = 0.0;
-                }
+            if selected_object < reference.current_scene.objects.len() && ui.button("Reset").clicked() {
+                reference.reset_rotation_axis(selected_object, 0);
+                reference.speed_slider.0 = 0.0;
             }
         }
         
@@ -809,11 +1455,9 @@ fn rotation_ui(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
         ui.add(egui::Slider::new(&mut reference.speed_slider.1, 0.0..=100.0));
 
         if let Some(selected_object) = reference.selected_object {
-            if selected_object < reference.current_scene.objects.len() {
-                if ui.button("Reset").clicked() {
-                    reference.current_scene.objects[selected_object].rotation[1] = 0.0;
-                    reference.speed_slider.1 = 0.0;
-                }
+            if selected_object < reference.current_scene.objects.len() && ui.button("Reset").clicked() {
+                reference.reset_rotation_axis(selected_object, 1);
+                reference.speed_slider.1 = 0.0;
             }
         }
         
@@ -824,11 +1468,9 @@ fn rotation_ui(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
         ui.add(egui::Slider::new(&mut reference.speed_slider.2, 0.0..=100.0));
 
         if let Some(selected_object) = reference.selected_object {
-            if selected_object < reference.current_scene.objects.len() {
-                if ui.button("Reset").clicked() {
-                    reference.current_scene.objects[selected_object].rotation[2] = 0.0;
-                    reference.speed_slider.2 = 0.0;
-                }
+            if selected_object < reference.current_scene.objects.len() && ui.button("Reset").clicked() {
+                reference.reset_rotation_axis(selected_object, 2);
+                reference.speed_slider.2 = 0.0;
             }
         }
         
@@ -846,47 +1488,225 @@ fn rotation_ui(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
 ui.add_space(10.0);
 }
 
-fn gerneral_settings(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
+fn gerneral_settings(ui: &mut Ui, reference : &mut Content) {
     ui.set_min_width(0.0);
     ui.add(TextEdit::singleline(&mut "Light Settings:").desired_width(110.0));
     ui.add_space(10.0);
 
+    let mut removed_light = None;
+    for (index, light) in reference.current_scene.lights.iter_mut().enumerate() {
+        CollapsingHeader::new(format!("Light {} ({:?})", index + 1, light.kind))
+        .id_source(("light", index))
+        .show(ui, |ui| {
+            light_ui(ui, light);
 
-        ui.add(TextEdit::singleline(&mut "Light Intensity:").desired_width(110.0));
-        ui.add(egui::DragValue::new(&mut reference.current_scene.light.intensity).speed(0.1));  
+            if ui.button("Remove").clicked() {
+                removed_light = Some(index);
+            }
+        });
+    }
+    if let Some(index) = removed_light {
+        let undo = Command::RemoveLight { index }.apply(&mut reference.current_scene);
+        reference.history.record(undo);
+    }
+
+    if ui.button("Add Light").clicked() {
+        let index = reference.current_scene.lights.len();
+        let undo = Command::InsertLight { index, light: Light::new(LightKind::Point) }.apply(&mut reference.current_scene);
+        reference.history.record(undo);
+    }
 
-        
     ui.add_space(4.0);
 
+        ui.add(TextEdit::singleline(&mut "Shading:").desired_width(110.0));
+
+        ui.horizontal(|ui| {
+        ui.selectable_value(&mut reference.renderer.shading, Shading::Flat, "Flat");
+        ui.selectable_value(&mut reference.renderer.shading, Shading::Gouraud, "Gouraud");
+        ui.selectable_value(&mut reference.renderer.shading, Shading::Phong, "Phong");
+        });
+}
+
+fn export_ui(ui: &mut Ui, reference : &mut Content) {
+    ui.add(TextEdit::singleline(&mut "Export:").desired_width(110.0));
+    ui.add(TextEdit::singleline(&mut reference.export_path).desired_width(150.0))
+        .on_hover_text("Ends in .obj or .stl");
+
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut reference.export_all, false, "Selected");
+        ui.selectable_value(&mut reference.export_all, true, "Whole scene");
+    });
+    ui.checkbox(&mut reference.bake_transforms, "Bake transforms");
+
+    if ui.button("Export").clicked() {
+        let count = reference.current_scene.objects.len();
+        let objects: Vec<usize> = if reference.export_all {
+            (0..count).collect()
+        } else {
+            reference.selected_object.filter(|index| *index < count).into_iter().collect()
+        };
+
+        let path = reference.export_path.clone();
+        if objects.is_empty() {
+            reference.log(LogLevel::Warning, "Select an object to export first".to_string());
+        } else {
+            match export_meshes(Path::new(&path), &reference.current_scene, &objects, reference.bake_transforms) {
+                Ok(()) => reference.log(LogLevel::Info, format!("Exported {} objects to {}", objects.len(), path)),
+                Err(err) => reference.log(LogLevel::Error, format!("Couldn't export {}: {}", path, err)),
+            }
+        }
+    }
+}
+
+fn material_ui(ui: &mut Ui, material: &mut Material) {
+    ui.horizontal(|ui| {
+        ui.color_edit_button_rgb(&mut material.diffuse);
+        ui.label("Diffuse");
+    });
+    ui.horizontal(|ui| {
+        ui.color_edit_button_rgb(&mut material.ambient);
+        ui.label("Ambient");
+    });
+    ui.horizontal(|ui| {
+        ui.color_edit_button_rgb(&mut material.specular);
+        ui.label("Specular");
+    });
+    ui.add(egui::Slider::new(&mut material.specular_strength, 0.0..=2.0).text("Strength"));
+    ui.add(egui::Slider::new(&mut material.shininess, 1.0..=512.0).logarithmic(true).text("Shininess"));
+    ui.horizontal(|ui| {
+        ui.color_edit_button_rgb(&mut material.emissive);
+        ui.label("Emissive");
+    });
+    ui.add(egui::Slider::new(&mut material.opacity, 0.0..=1.0).text("Opacity"));
+
+    if let Some(size) = material.texture.as_ref().map(|texture| (texture.width, texture.height)) {
+        ui.horizontal(|ui| {
+            ui.label(format!("Texture {}x{}", size.0, size.1));
+            if ui.button("Remove").clicked() {
+                material.texture = None;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut material.texture_filter, TextureFilter::Nearest, "Nearest");
+            ui.selectable_value(&mut material.texture_filter, TextureFilter::Bilinear, "Bilinear");
+        });
+    } else {
+        ui.label("Drop a PNG or JPEG to add a texture");
+    }
+
+    if ui.button("Reset").clicked() {
+        *material = Material::default();
+    }
+}
+
+fn light_ui(ui: &mut Ui, light: &mut Light) {
+    ui.horizontal_wrapped(|ui| {
+        ui.selectable_value(&mut light.kind, LightKind::Point, "Point");
+        ui.selectable_value(&mut light.kind, LightKind::Directional, "Directional");
+        ui.selectable_value(&mut light.kind, LightKind::Spot, "Spot");
+        ui.selectable_value(&mut light.kind, LightKind::Ambient, "Ambient");
+    });
+
+    ui.horizontal(|ui| {
+        ui.color_edit_button_rgb(&mut light.color);
+        ui.add(egui::DragValue::new(&mut light.intensity).speed(0.1).clamp_range(0.0..=f32::MAX));
+    });
+
+    if matches!(light.kind, LightKind::Point | LightKind::Spot) {
         ui.add(TextEdit::singleline(&mut "Light Position:").desired_width(110.0));
 
         ui.horizontal(|ui| {
-        ui.add(egui::DragValue::new(&mut reference.current_scene.light.position[0]).speed(0.1));  
-        ui.add(egui::DragValue::new(&mut reference.current_scene.light.position[1]).speed(0.1));  
-        ui.add(egui::DragValue::new(&mut reference.current_scene.light.position[2]).speed(0.1));  
-        });   
+        ui.add(egui::DragValue::new(&mut light.position[0]).speed(0.1));  
+        ui.add(egui::DragValue::new(&mut light.position[1]).speed(0.1));  
+        ui.add(egui::DragValue::new(&mut light.position[2]).speed(0.1));  
+        });
+
+        ui.add(TextEdit::singleline(&mut "Falloff (c, l, q):").desired_width(110.0));
+
+        ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut light.attenuation[0]).speed(0.01).clamp_range(0.0..=f32::MAX));  
+        ui.add(egui::DragValue::new(&mut light.attenuation[1]).speed(0.01).clamp_range(0.0..=f32::MAX));  
+        ui.add(egui::DragValue::new(&mut light.attenuation[2]).speed(0.01).clamp_range(0.0..=f32::MAX));  
+        });
+    }
+
+    if matches!(light.kind, LightKind::Directional | LightKind::Spot) {
+        ui.add(TextEdit::singleline(&mut "Light Direction:").desired_width(110.0));
+
+        ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut light.direction[0]).speed(0.05));  
+        ui.add(egui::DragValue::new(&mut light.direction[1]).speed(0.05));  
+        ui.add(egui::DragValue::new(&mut light.direction[2]).speed(0.05));  
+        });
+    }
+
+    if light.kind == LightKind::Spot {
+        ui.add(TextEdit::singleline(&mut "Cone (inner, outer):").desired_width(110.0));
+
+        ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut light.inner_angle).speed(0.5).clamp_range(0.0..=90.0).suffix("°"));  
+        ui.add(egui::DragValue::new(&mut light.outer_angle).speed(0.5).clamp_range(0.0..=90.0).suffix("°"));  
+        });
+    }
 }
 
-fn camera_settings(ui: &mut Ui, reference : &mut Content, deltaTime: f32) {
+fn camera_settings(ui: &mut Ui, reference : &mut Content) {
     ui.add(TextEdit::singleline(&mut "Camera Settings:").desired_width(110.0));
     ui.add_space(10.0);
 
+    ui.add(TextEdit::singleline(&mut "Controls:").desired_width(110.0));
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut reference.camera_mode, CameraMode::Orbit, "Orbit")
+            .on_hover_text("Left drag turns around, middle drag pans, scrolling zooms");
+        ui.selectable_value(&mut reference.camera_mode, CameraMode::Fly, "Fly")
+            .on_hover_text("Right drag looks around, WASD moves, Q and E go down and up, Shift is faster");
+    });
+    ui.add_space(4.0);
+
     ui.add(TextEdit::singleline(&mut "Position:").desired_width(110.0));
     ui.add_space(4.0);
 
     ui.horizontal(|ui| {
-        ui.add(egui::DragValue::new(&mut reference.current_scene.camera_position[0]).speed(0.05));  
-        ui.add(egui::DragValue::new(&mut reference.current_scene.camera_position[1]).speed(0.05));  
-        ui.add(egui::DragValue::new(&mut reference.current_scene.camera_position[2]).speed(0.05));  
+        ui.add(egui::DragValue::new(&mut reference.current_scene.camera.position[0]).speed(0.05));  
+        ui.add(egui::DragValue::new(&mut reference.current_scene.camera.position[1]).speed(0.05));  
+        ui.add(egui::DragValue::new(&mut reference.current_scene.camera.position[2]).speed(0.05));  
     });
 
     ui.add(TextEdit::singleline(&mut "Rotation:").desired_width(110.0));
     ui.add_space(4.0);
 
     ui.horizontal(|ui| {
-        ui.add(egui::DragValue::new(&mut reference.current_scene.camera_rotation[0]).speed(0.05));  
-        ui.add(egui::DragValue::new(&mut reference.current_scene.camera_rotation[1]).speed(0.05));  
-        ui.add(egui::DragValue::new(&mut reference.current_scene.camera_rotation[2]).speed(0.05));  
+        ui.add(egui::DragValue::new(&mut reference.current_scene.camera.rotation[0]).speed(0.05));  
+        ui.add(egui::DragValue::new(&mut reference.current_scene.camera.rotation[1]).speed(0.05));  
+        ui.add(egui::DragValue::new(&mut reference.current_scene.camera.rotation[2]).speed(0.05));  
+    });
+
+    ui.add_space(4.0);
+    ui.add(TextEdit::singleline(&mut "Projection:").desired_width(110.0));
+
+    let camera = &mut reference.current_scene.camera;
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut camera.projection, Projection::Perspective, "Perspective");
+        ui.selectable_value(&mut camera.projection, Projection::Orthographic, "Ortho");
+    });
+
+    match camera.projection {
+        Projection::Perspective => {
+            ui.add(TextEdit::singleline(&mut "Field of View:").desired_width(110.0));
+            ui.add(egui::Slider::new(&mut camera.fov, 10.0..=120.0).suffix("°"));
+        }
+        Projection::Orthographic => {
+            ui.add(TextEdit::singleline(&mut "View Height:").desired_width(110.0));
+            ui.add(egui::DragValue::new(&mut camera.ortho_height).speed(0.1).clamp_range(0.1..=1000.0));
+        }
+    }
+
+    ui.add(TextEdit::singleline(&mut "Near / Far:").desired_width(110.0));
+    ui.horizontal(|ui| {
+        let far = camera.far;
+        ui.add(egui::DragValue::new(&mut camera.near).speed(0.01).clamp_range(0.01..=far - 0.01));
+        let near = camera.near;
+        ui.add(egui::DragValue::new(&mut camera.far).speed(1.0).clamp_range(near + 0.01..=100000.0));
     });
  }
 
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::texture::{Texture, TextureFilter};
+
+// How a surface reacts to light. Colors are RGB from 0 to 1.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    // Color under direct light
+    pub diffuse: [f32; 3],
+    // Multiplied with the ambient lights
+    pub ambient: [f32; 3],
+    // Color, brightness and tightness of highlights. Higher shininess gives smaller, sharper highlights
+    pub specular: [f32; 3],
+    pub specular_strength: f32,
+    pub shininess: f32,
+    // Light the surface gives off by itself, shows up even in the dark
+    pub emissive: [f32; 3],
+    // 1 is solid, anything less lets what's behind the surface show through
+    pub opacity: f32,
+    // Image that tints the diffuse and ambient colors, shared so copies of a material stay cheap
+    pub texture: Option<Arc<Texture>>,
+    pub texture_filter: TextureFilter,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            diffuse: [1.0, 1.0, 1.0],
+            ambient: [1.0, 1.0, 1.0],
+            specular: [1.0, 1.0, 1.0],
+            specular_strength: 0.5,
+            shininess: 32.0,
+            emissive: [0.0, 0.0, 0.0],
+            opacity: 1.0,
+            texture: None,
+            texture_filter: TextureFilter::default(),
+        }
+    }
+}
@@ -0,0 +1,327 @@
+pub fn calculate_normal(vertex_a: [f32; 3], vertex_b: [f32; 3], vertex_c: [f32; 3]) -> [f32; 3] {
+    // Calculate the cross product of two edges of the triangle to find the normal vector
+    let edge1 = [
+        vertex_b[0] - vertex_a[0],
+        vertex_b[1] - vertex_a[1],
+        vertex_b[2] - vertex_a[2],
+    ];
+    let edge2 = [
+        vertex_c[0] - vertex_a[0],
+        vertex_c[1] - vertex_a[1],
+        vertex_c[2] - vertex_a[2],
+    ];
+
+    let normal = [
+        edge1[1] * edge2[2] - edge1[2] * edge2[1],
+        edge1[2] * edge2[0] - edge1[0] * edge2[2],
+        edge1[0] * edge2[1] - edge1[1] * edge2[0],
+    ];
+
+    // Normalize the normal vector
+    let normal_length = f32::sqrt(normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]);
+    [
+        normal[0] / normal_length,
+        normal[1] / normal_length,
+        normal[2] / normal_length,
+    ]
+}
+
+pub fn vec3_add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub fn vec3_sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn vec3_scale(v: [f32; 3], factor: f32) -> [f32; 3] {
+    [v[0] * factor, v[1] * factor, v[2] * factor]
+}
+
+pub fn vec3_dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn vec3_cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn vec3_normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = vec3_dot(v, v).sqrt();
+    if length == 0.0 {
+        return v;
+    }
+    [v[0] / length, v[1] / length, v[2] / length]
+}
+
+// Row major 4x4 matrix, m[row][column]. Vectors are columns, so `mat4_mul(a, b)` applies b first.
+pub type Mat4 = [[f32; 4]; 4];
+
+pub fn mat4_identity() -> Mat4 {
+    [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+pub fn mat4_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut result = [[0.0; 4]; 4];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    result
+}
+
+pub fn mat4_transform(m: &Mat4, v: [f32; 4]) -> [f32; 4] {
+    let mut result = [0.0; 4];
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = m[row][0] * v[0] + m[row][1] * v[1] + m[row][2] * v[2] + m[row][3] * v[3];
+    }
+    result
+}
+
+// Moves a point, translation included
+pub fn mat4_transform_point(m: &Mat4, point: [f32; 3]) -> [f32; 3] {
+    let result = mat4_transform(m, [point[0], point[1], point[2], 1.0]);
+    [result[0], result[1], result[2]]
+}
+
+// Turns a direction, translation left out
+pub fn mat4_transform_direction(m: &Mat4, direction: [f32; 3]) -> [f32; 3] {
+    let result = mat4_transform(m, [direction[0], direction[1], direction[2], 0.0]);
+    [result[0], result[1], result[2]]
+}
+
+// General inverse by cofactors, None when the matrix squashes space flat
+pub fn mat4_inverse(m: &Mat4) -> Option<Mat4> {
+    // 2x2 determinants of the top two and bottom two rows, reused by every cofactor
+    let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+    let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+    let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+    let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+    let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+    let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+
+    let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+    let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+    let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+    let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+    let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+    let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+
+    let determinant = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inv = 1.0 / determinant;
+
+    Some([
+        [
+            (m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * inv,
+            (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * inv,
+            (m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * inv,
+            (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * inv,
+        ],
+        [
+            (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * inv,
+            (m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * inv,
+            (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * inv,
+            (m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * inv,
+        ],
+        [
+            (m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * inv,
+            (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * inv,
+            (m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * inv,
+            (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * inv,
+        ],
+        [
+            (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * inv,
+            (m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * inv,
+            (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * inv,
+            (m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * inv,
+        ],
+    ])
+}
+
+pub fn mat4_translation(offset: [f32; 3]) -> Mat4 {
+    let mut result = mat4_identity();
+    result[0][3] = offset[0];
+    result[1][3] = offset[1];
+    result[2][3] = offset[2];
+    result
+}
+
+// Rotations take radians and are counterclockwise when looking down the axis towards the origin
+pub fn mat4_rotation_x(angle: f32) -> Mat4 {
+    let (sin, cos) = angle.sin_cos();
+    [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, cos, -sin, 0.0],
+        [0.0, sin, cos, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+pub fn mat4_rotation_y(angle: f32) -> Mat4 {
+    let (sin, cos) = angle.sin_cos();
+    [
+        [cos, 0.0, sin, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [-sin, 0.0, cos, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+pub fn mat4_rotation_z(angle: f32) -> Mat4 {
+    let (sin, cos) = angle.sin_cos();
+    [
+        [cos, -sin, 0.0, 0.0],
+        [sin, cos, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+// OpenGL style projection: the camera looks down -Z and visible depth ends up in -w..w
+pub fn mat4_perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+    let f = 1.0 / (fov_y / 2.0).tan();
+    [
+        [f / aspect, 0.0, 0.0, 0.0],
+        [0.0, f, 0.0, 0.0],
+        [0.0, 0.0, (far + near) / (near - far), 2.0 * far * near / (near - far)],
+        [0.0, 0.0, -1.0, 0.0],
+    ]
+}
+
+pub fn mat4_orthographic(half_width: f32, half_height: f32, near: f32, far: f32) -> Mat4 {
+    [
+        [1.0 / half_width, 0.0, 0.0, 0.0],
+        [0.0, 1.0 / half_height, 0.0, 0.0],
+        [0.0, 0.0, -2.0 / (far - near), -(far + near) / (far - near)],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+pub fn mat4_scale(scale: [f32; 3]) -> Mat4 {
+    let mut result = mat4_identity();
+    result[0][0] = scale[0];
+    result[1][1] = scale[1];
+    result[2][2] = scale[2];
+    result
+}
+
+pub fn mat4_transpose(m: &Mat4) -> Mat4 {
+    let mut result = [[0.0; 4]; 4];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = m[column][row];
+        }
+    }
+    result
+}
+
+// Determinant of the 3x3 part, negative when the matrix mirrors things
+pub fn mat4_determinant3(m: &Mat4) -> f32 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+// What normals have to be multiplied with so they stay at right angles to a surface
+// that has been scaled unevenly. Normalize them afterwards.
+pub fn mat4_normal_matrix(m: &Mat4) -> Mat4 {
+    match mat4_inverse(m) {
+        Some(inverse) => mat4_transpose(&inverse),
+        None => *m,
+    }
+}
+
+// Unit quaternion [x, y, z, w]
+pub type Quat = [f32; 4];
+
+pub fn quat_identity() -> Quat {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+// Angle in radians, the axis doesn't have to be normalized
+pub fn quat_from_axis_angle(axis: [f32; 3], angle: f32) -> Quat {
+    let axis = vec3_normalize(axis);
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    [axis[0] * sin, axis[1] * sin, axis[2] * sin, cos]
+}
+
+// Like `mat4_mul`, `quat_mul(a, b)` rotates by b first and then by a
+pub fn quat_mul(a: Quat, b: Quat) -> Quat {
+    [
+        a[3] * b[0] + a[0] * b[3] + a[1] * b[2] - a[2] * b[1],
+        a[3] * b[1] - a[0] * b[2] + a[1] * b[3] + a[2] * b[0],
+        a[3] * b[2] + a[0] * b[1] - a[1] * b[0] + a[2] * b[3],
+        a[3] * b[3] - a[0] * b[0] - a[1] * b[1] - a[2] * b[2],
+    ]
+}
+
+pub fn quat_normalize(q: Quat) -> Quat {
+    let length = (q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt();
+    if length == 0.0 {
+        return quat_identity();
+    }
+    q.map(|value| value / length)
+}
+
+pub fn quat_to_mat4(q: Quat) -> Mat4 {
+    let [x, y, z, w] = q;
+    [
+        [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), 0.0],
+        [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), 0.0],
+        [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+// Splits an affine matrix into a translation, Euler angles in radians in `EulerOrder::Xyz` order and
+// a scale per axis.
+// Shear can't be represented and gets lost, a mirrored matrix comes back with a negative x scale.
+pub fn mat4_decompose(m: &Mat4) -> ([f32; 3], [f32; 3], [f32; 3]) {
+    let translation = [m[0][3], m[1][3], m[2][3]];
+
+    let mut scale = [0.0; 3];
+    for (column, value) in scale.iter_mut().enumerate() {
+        *value = (m[0][column] * m[0][column] + m[1][column] * m[1][column] + m[2][column] * m[2][column]).sqrt();
+    }
+
+    if mat4_determinant3(m) < 0.0 {
+        scale[0] = -scale[0];
+    }
+
+    // What's left once the scale is divided out of each column is a pure rotation
+    let mut rotation = [[0.0; 3]; 3];
+    for (row, rotation_row) in rotation.iter_mut().enumerate() {
+        for (column, value) in rotation_row.iter_mut().enumerate() {
+            if scale[column] != 0.0 {
+                *value = m[row][column] / scale[column];
+            }
+        }
+    }
+
+    // The rotation is Rz * Ry * Rx, read the angles back out of that product
+    let sin_y = (-rotation[2][0]).clamp(-1.0, 1.0);
+    let angles = if sin_y.abs() < 0.9999 {
+        [
+            rotation[2][1].atan2(rotation[2][2]),
+            sin_y.asin(),
+            rotation[1][0].atan2(rotation[0][0]),
+        ]
+    } else {
+        // Gimbal lock, x and z turn around the same axis so z can take all of it
+        [0.0, sin_y.asin(), (-rotation[0][1]).atan2(rotation[1][1])]
+    };
+
+    (translation, angles, scale)
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use crate::math::{calculate_normal, vec3_dot};
+use crate::scene::Mesh;
+
+// Faces meeting at a sharper angle than this (in degrees) keep a hard edge
+pub const DEFAULT_CREASE_ANGLE: f32 = 60.0;
+
+// Replaces the vertex normals of a mesh with the average of the faces around each vertex.
+// Only faces within `crease_angle` degrees of each other get averaged, so a cube stays
+// a cube while a sphere comes out smooth. Vertices get split wherever an edge stays hard.
+pub fn generate_normals(mesh: &mut Mesh, crease_angle: f32) {
+    let cos_threshold = crease_angle.to_radians().cos();
+
+    // Vertices may already be split by uvs or old normals, so weld them back together by
+    // position to find every face that touches the same point
+    let mut welded: HashMap<[u32; 3], usize> = HashMap::new();
+    let point_of_vertex: Vec<usize> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let key = [vertex.0.to_bits(), vertex.1.to_bits(), vertex.2.to_bits()];
+            let next = welded.len();
+            *welded.entry(key).or_insert(next)
+        })
+        .collect();
+
+    let face_normals: Vec<[f32; 3]> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|face| {
+            let [a, b, c] = [0, 1, 2].map(|k| {
+                let vertex = mesh.vertices[face[k] as usize];
+                [vertex.0, vertex.1, vertex.2]
+            });
+            let normal = calculate_normal(a, b, c);
+            // Degenerate faces don't get a say
+            if normal.iter().all(|value| value.is_finite()) {
+                normal
+            } else {
+                [0.0, 0.0, 0.0]
+            }
+        })
+        .collect();
+
+    let mut faces_at_point: Vec<Vec<usize>> = vec![vec![]; welded.len()];
+    for (face, corners) in mesh.indices.chunks_exact(3).enumerate() {
+        for index in corners {
+            faces_at_point[point_of_vertex[*index as usize]].push(face);
+        }
+    }
+
+    let mut vertices = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    let mut colors = vec![];
+    let mut indices = Vec::with_capacity(mesh.indices.len());
+    let mut lookup: HashMap<(usize, [u32; 3]), u32> = HashMap::new();
+
+    for (face, corners) in mesh.indices.chunks_exact(3).enumerate() {
+        let face_normal = face_normals[face];
+
+        for index in corners {
+            let point = point_of_vertex[*index as usize];
+
+            let mut sum = [0.0, 0.0, 0.0];
+            for other in &faces_at_point[point] {
+                let other_normal = face_normals[*other];
+                if vec3_dot(face_normal, other_normal) >= cos_threshold {
+                    sum = [sum[0] + other_normal[0], sum[1] + other_normal[1], sum[2] + other_normal[2]];
+                }
+            }
+
+            let length = vec3_dot(sum, sum).sqrt();
+            let normal = if length > 0.0 {
+                (sum[0] / length, sum[1] / length, sum[2] / length)
+            } else {
+                (face_normal[0], face_normal[1], face_normal[2])
+            };
+
+            // Keep the original vertex so anything else stored on it survives
+            let key = (*index as usize, [normal.0.to_bits(), normal.1.to_bits(), normal.2.to_bits()]);
+            let new_index = *lookup.entry(key).or_insert_with(|| {
+                vertices.push(mesh.vertices[*index as usize]);
+                normals.push(normal);
+                if let Some(uv) = mesh.uvs.get(*index as usize) {
+                    uvs.push(*uv);
+                }
+                if let Some(color) = mesh.colors.get(*index as usize) {
+                    colors.push(*color);
+                }
+                (vertices.len() - 1) as u32
+            });
+            indices.push(new_index);
+        }
+    }
+
+    mesh.vertices = vertices;
+    mesh.normals = normals;
+    mesh.uvs = uvs;
+    mesh.colors = colors;
+    mesh.indices = indices;
+}
@@ -0,0 +1,124 @@
+// Finding out what is under the mouse. A ray goes from the camera through a point on the screen
+// and is tested against every triangle in the scene, the closest one it hits wins.
+
+use crate::camera::{Camera, Projection};
+use crate::math::*;
+use crate::scene::Scene;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: [f32; 3],
+    // Always one unit long, so distances along the ray are world units
+    pub direction: [f32; 3],
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> [f32; 3] {
+        vec3_add(self.origin, vec3_scale(self.direction, distance))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub object: usize,
+    pub distance: f32,
+}
+
+// The ray through pixel coordinates `x`, `y` of a `width` x `height` image the camera renders
+pub fn screen_ray(camera: &Camera, x: f32, y: f32, width: f32, height: f32) -> Ray {
+    // -1 to 1 across the screen, up is positive
+    let screen_x = x / width * 2.0 - 1.0;
+    let screen_y = 1.0 - y / height * 2.0;
+    let aspect = width / height;
+    let right = camera.right();
+    let up = camera.up();
+    let forward = camera.forward();
+
+    match camera.projection {
+        Projection::Perspective => {
+            let half_height = (camera.fov.to_radians() / 2.0).tan();
+            let offset = vec3_add(
+                vec3_scale(right, screen_x * half_height * aspect),
+                vec3_scale(up, screen_y * half_height),
+            );
+            Ray { origin: camera.position, direction: vec3_normalize(vec3_add(forward, offset)) }
+        }
+        // Every ray points the same way, they start spread out over the view instead
+        Projection::Orthographic => {
+            let half_height = camera.ortho_height / 2.0;
+            let offset = vec3_add(
+                vec3_scale(right, screen_x * half_height * aspect),
+                vec3_scale(up, screen_y * half_height),
+            );
+            Ray { origin: vec3_add(camera.position, offset), direction: forward }
+        }
+    }
+}
+
+// The other way around: where a world space point shows up in the image, None behind the camera
+pub fn world_to_screen(camera: &Camera, point: [f32; 3], width: f32, height: f32) -> Option<[f32; 2]> {
+    let view_projection = mat4_mul(&camera.projection_matrix(width / height), &camera.view_matrix());
+    let clip = mat4_transform(&view_projection, [point[0], point[1], point[2], 1.0]);
+    if clip[3] < 1e-5 {
+        return None;
+    }
+    Some([
+        (clip[0] / clip[3] * 0.5 + 0.5) * width,
+        (0.5 - clip[1] / clip[3] * 0.5) * height,
+    ])
+}
+
+// Möller–Trumbore. Returns how far along the ray the triangle is hit, from either side.
+pub fn intersect_triangle(ray: &Ray, a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Option<f32> {
+    let edge_ab = vec3_sub(b, a);
+    let edge_ac = vec3_sub(c, a);
+    let p = vec3_cross(ray.direction, edge_ac);
+    let determinant = vec3_dot(edge_ab, p);
+    // The ray runs along the triangle
+    if determinant.abs() < 1e-8 {
+        return None;
+    }
+
+    let inverse = 1.0 / determinant;
+    let to_origin = vec3_sub(ray.origin, a);
+    let u = vec3_dot(to_origin, p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = vec3_cross(to_origin, edge_ab);
+    let v = vec3_dot(ray.direction, q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = vec3_dot(edge_ac, q) * inverse;
+    (distance > 1e-6).then_some(distance)
+}
+
+// The closest object the ray hits, with every mesh where it is drawn
+pub fn pick(scene: &Scene, ray: &Ray) -> Option<Hit> {
+    let world_matrices = scene.world_matrices();
+    let mut closest: Option<Hit> = None;
+
+    for (object, mesh) in scene.objects.iter().enumerate() {
+        let world = &world_matrices[object];
+        let vertices: Vec<[f32; 3]> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| mat4_transform_point(world, [vertex.0, vertex.1, vertex.2]))
+            .collect();
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|corner| vertices[triangle[corner] as usize]);
+            let Some(distance) = intersect_triangle(ray, corners[0], corners[1], corners[2]) else {
+                continue;
+            };
+            if closest.is_none_or(|hit| distance < hit.distance) {
+                closest = Some(Hit { object, distance });
+            }
+        }
+    }
+
+    closest
+}
@@ -0,0 +1,195 @@
+// Software rasterizer with a per-pixel depth test.
+// Colors are stored as tightly packed RGBA bytes so the buffer can be handed
+// straight to egui (or the image crate) without any conversion.
+
+// Values that get interpolated across a triangle for per-pixel shading
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Varyings {
+    // World space position
+    pub world: [f32; 3],
+    pub normal: [f32; 3],
+    // Texture coordinates
+    pub uv: [f32; 2],
+    // Vertex color, multiplied with the texture
+    pub tint: [f32; 3],
+    // Lighting that was already worked out per vertex, see `Illumination`
+    pub color: [f32; 3],
+    pub specular: [f32; 3],
+}
+
+impl Varyings {
+    pub fn lerp(&self, other: &Varyings, t: f32) -> Varyings {
+        Varyings::weighted([self, other], [1.0 - t, t])
+    }
+
+    fn weighted<const N: usize>(values: [&Varyings; N], weights: [f32; N]) -> Varyings {
+        let mut result = Varyings::default();
+        for (value, weight) in values.iter().zip(weights) {
+            for i in 0..3 {
+                result.world[i] += value.world[i] * weight;
+                result.normal[i] += value.normal[i] * weight;
+                result.color[i] += value.color[i] * weight;
+                result.specular[i] += value.specular[i] * weight;
+                result.tint[i] += value.tint[i] * weight;
+            }
+            for i in 0..2 {
+                result.uv[i] += value.uv[i] * weight;
+            }
+        }
+        result
+    }
+}
+
+// A vertex that has been projected onto the screen
+#[derive(Clone, Copy, Debug)]
+pub struct ScreenVertex {
+    // Pixel coordinates and depth, like `draw_triangle`
+    pub position: [f32; 3],
+    // 1 / w from clip space, needed for perspective correct interpolation
+    pub inv_w: f32,
+    pub varyings: Varyings,
+}
+
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub color: Vec<u8>,
+    pub depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            color: vec![0; width * height * 4],
+            depth: vec![f32::INFINITY; width * height],
+        }
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if self.width != width || self.height != height {
+            *self = Framebuffer::new(width, height);
+        }
+    }
+
+    pub fn clear(&mut self, color: [u8; 4]) {
+        for pixel in self.color.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+        self.depth.fill(f32::INFINITY);
+    }
+
+    // Vertices are in pixel coordinates (x right, y down) with the depth in z.
+    // Smaller depth values are closer to the camera. Colors with an alpha below 255 are blended.
+    pub fn draw_triangle(&mut self, vertices: [[f32; 3]; 3], color: [u8; 4]) {
+        self.rasterize(vertices, |_| color);
+    }
+
+    // Like `draw_triangle`, but asks `shade` for the color of every pixel that passes the
+    // depth test. The varyings handed to it are interpolated with perspective correction.
+    pub fn draw_shaded_triangle<F>(&mut self, vertices: [ScreenVertex; 3], mut shade: F)
+    where
+        F: FnMut(&Varyings) -> [u8; 4],
+    {
+        self.rasterize(vertices.map(|vertex| vertex.position), |weights| {
+            // Screen space weights are skewed by perspective, undo that with 1/w
+            let p0 = weights[0] * vertices[0].inv_w;
+            let p1 = weights[1] * vertices[1].inv_w;
+            let p2 = weights[2] * vertices[2].inv_w;
+            let sum = p0 + p1 + p2;
+
+            let varyings = Varyings::weighted(
+                [&vertices[0].varyings, &vertices[1].varyings, &vertices[2].varyings],
+                [p0 / sum, p1 / sum, p2 / sum],
+            );
+            shade(&varyings)
+        });
+    }
+
+    // Walks every pixel covered by the triangle and depth tests it.
+    // `pixel` gets the barycentric weights of the pixel and returns its color.
+    fn rasterize<F>(&mut self, vertices: [[f32; 3]; 3], mut pixel: F)
+    where
+        F: FnMut([f32; 3]) -> [u8; 4],
+    {
+        let [v0, v1, v2] = vertices;
+
+        let area = edge(v0, v1, v2);
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        // Bounding box of the triangle, clamped to the framebuffer
+        let min_x = v0[0].min(v1[0]).min(v2[0]).floor().max(0.0) as usize;
+        let min_y = v0[1].min(v1[1]).min(v2[1]).floor().max(0.0) as usize;
+        let max_x = v0[0].max(v1[0]).max(v2[0]).ceil().min(self.width as f32 - 1.0);
+        let max_y = v0[1].max(v1[1]).max(v2[1]).ceil().min(self.height as f32 - 1.0);
+
+        if max_x < 0.0 || max_y < 0.0 {
+            return;
+        }
+        let max_x = max_x as usize;
+        let max_y = max_y as usize;
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                // Sample at the pixel center
+                let p = [x as f32 + 0.5, y as f32 + 0.5, 0.0];
+
+                // Barycentric weights, normalized so winding order doesn't matter
+                let w0 = edge(v1, v2, p) / area;
+                let w1 = edge(v2, v0, p) / area;
+                let w2 = edge(v0, v1, p) / area;
+
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let depth = w0 * v0[2] + w1 * v1[2] + w2 * v2[2];
+                let index = y * self.width + x;
+
+                if depth < self.depth[index] {
+                    let color = pixel([w0, w1, w2]);
+                    let target = &mut self.color[index * 4..index * 4 + 4];
+
+                    // See-through pixels get blended over what's already there and don't hide
+                    // anything drawn after them, so they should be drawn last
+                    if color[3] == 255 {
+                        self.depth[index] = depth;
+                        target.copy_from_slice(&color);
+                    } else {
+                        let blended = blend(color, [target[0], target[1], target[2], target[3]]);
+                        target.copy_from_slice(&blended);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Twice the signed area of the triangle (a, b, c)
+fn edge(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+// Puts `source` over `destination`, both straight (not premultiplied) RGBA
+fn blend(source: [u8; 4], destination: [u8; 4]) -> [u8; 4] {
+    let source_alpha = source[3] as f32 / 255.0;
+    let destination_alpha = destination[3] as f32 / 255.0 * (1.0 - source_alpha);
+    let alpha = source_alpha + destination_alpha;
+    if alpha <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+
+    let mut result = [0, 0, 0, (alpha * 255.0).round() as u8];
+    for i in 0..3 {
+        let channel = (source[i] as f32 * source_alpha + destination[i] as f32 * destination_alpha) / alpha;
+        result[i] = channel.round() as u8;
+    }
+    result
+}
@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+
+use crate::clipping::{clip_triangle, ClipVertex};
+use crate::camera::Projection;
+use crate::lighting::{calculate_lighting, calculate_point_lighting, color_to_rgba, Eye, Illumination};
+use crate::material::Material;
+use crate::math::*;
+use crate::rasterizer::{Framebuffer, ScreenVertex, Varyings};
+use crate::scene::{CullMode, Scene};
+
+// Anything that can turn a scene into pixels.
+// The GUI and the headless renderer only talk to this, so other backends can be swapped in.
+pub trait Renderer {
+    // Draws the scene at the given resolution and returns the finished frame
+    fn render(&mut self, scene: &Scene, width: usize, height: usize) -> &Framebuffer;
+}
+
+// Triangle counts from the last frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub triangles: usize,
+    pub culled: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Shading {
+    // One color per triangle
+    Flat,
+    // Lighting per vertex, blended across the triangle
+    #[default]
+    Gouraud,
+    // Lighting per pixel from the interpolated vertex normals
+    Phong,
+}
+
+// Software renderer, everything happens on the CPU into a framebuffer
+pub struct CpuRenderer {
+    // Transparent by default so whatever is behind the frame shows through
+    pub clear_color: [u8; 4],
+    pub shading: Shading,
+    // Object tinted so it stands out, the GUI points this at the selection
+    pub highlight: Option<usize>,
+    framebuffer: Framebuffer,
+    stats: RenderStats,
+}
+
+impl CpuRenderer {
+    pub fn new() -> Self {
+        CpuRenderer {
+            clear_color: [0, 0, 0, 0],
+            shading: Shading::default(),
+            highlight: None,
+            framebuffer: Framebuffer::new(0, 0),
+            stats: RenderStats::default(),
+        }
+    }
+
+    // The last rendered frame
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+}
+
+impl Default for CpuRenderer {
+    fn default() -> Self {
+        CpuRenderer::new()
+    }
+}
+
+impl Renderer for CpuRenderer {
+    fn render(&mut self, scene: &Scene, width: usize, height: usize) -> &Framebuffer {
+        self.framebuffer.resize(width, height);
+        self.stats = render_scene(scene, &mut self.framebuffer, self.clear_color, self.shading, self.highlight);
+        &self.framebuffer
+    }
+}
+
+// Color the highlighted object is pulled toward, and how far
+const HIGHLIGHT_COLOR: [f32; 3] = [255.0, 150.0, 30.0];
+const HIGHLIGHT_AMOUNT: f32 = 0.35;
+
+fn render_scene(
+    scene: &Scene,
+    framebuffer: &mut Framebuffer,
+    clear_color: [u8; 4],
+    shading: Shading,
+    highlight: Option<usize>,
+) -> RenderStats {
+    let canvas_width = framebuffer.width as f32;
+    let canvas_height = framebuffer.height as f32;
+    let mut stats = RenderStats::default();
+
+    framebuffer.clear(clear_color);
+
+    if canvas_width == 0.0 || canvas_height == 0.0 {
+        return stats;
+    }
+
+    let aspect = canvas_width / canvas_height;
+    let view_projection = mat4_mul(
+        &scene.camera.projection_matrix(aspect),
+        &scene.camera.view_matrix(),
+    );
+
+    let eye = Eye::from_camera(&scene.camera);
+
+    // Children are placed relative to their parents, all the way up the tree
+    let world_matrices = scene.world_matrices();
+
+    // Solid meshes go first so see-through ones have something to blend with
+    let objects = &scene.objects;
+    let solid = (0..objects.len()).filter(|index| objects[*index].material.opacity >= 1.0);
+    let see_through = (0..objects.len()).filter(|index| objects[*index].material.opacity < 1.0);
+
+    for object in solid.chain(see_through) {
+        let mesh = &objects[object];
+        let world = &world_matrices[object];
+        let normal_matrix = mat4_normal_matrix(world);
+        // A mirroring scale turns the triangles inside out, so their corners get swapped back
+        let mirrored = mat4_determinant3(world) < 0.0;
+        let highlighted = highlight == Some(object);
+        let vertices = &mesh.vertices;
+        let indices = &mesh.indices;
+        stats.triangles += indices.len() / 3;
+
+        for i in (0..indices.len()).step_by(3) {
+            let a = indices[i] as usize;
+            let (b, c) = if mirrored {
+                (indices[i + 2] as usize, indices[i + 1] as usize)
+            } else {
+                (indices[i + 1] as usize, indices[i + 2] as usize)
+            };
+            let vertex_a = &vertices[a];
+            let vertex_b = &vertices[b];
+            let vertex_c = &vertices[c];
+
+            let pose_a = mat4_transform_point(world, [vertex_a.0, vertex_a.1, vertex_a.2]);
+            let pose_b = mat4_transform_point(world, [vertex_b.0, vertex_b.1, vertex_b.2]);
+            let pose_c = mat4_transform_point(world, [vertex_c.0, vertex_c.1, vertex_c.2]);
+
+            if is_culled(scene, mesh.cull_mode, pose_a, pose_b, pose_c) {
+                stats.culled += 1;
+                continue;
+            }
+
+            let clip_a = mat4_transform(&view_projection, [pose_a[0], pose_a[1], pose_a[2], 1.0]);
+            let clip_b = mat4_transform(&view_projection, [pose_b[0], pose_b[1], pose_b[2], 1.0]);
+            let clip_c = mat4_transform(&view_projection, [pose_c[0], pose_c[1], pose_c[2], 1.0]);
+
+            let mut triangle = [
+                ClipVertex { position: clip_a, varyings: Varyings { world: pose_a, ..Default::default() } },
+                ClipVertex { position: clip_b, varyings: Varyings { world: pose_b, ..Default::default() } },
+                ClipVertex { position: clip_c, varyings: Varyings { world: pose_c, ..Default::default() } },
+            ];
+
+            // Meshes without texture coordinates just sample the corner of the texture
+            for (vertex, index) in triangle.iter_mut().zip([a, b, c]) {
+                let uv = mesh.uvs.get(index).copied().unwrap_or((0.0, 0.0));
+                vertex.varyings.uv = [uv.0, uv.1];
+                vertex.varyings.tint = mesh.colors.get(index).copied().unwrap_or([1.0, 1.0, 1.0]);
+            }
+
+            if shading == Shading::Flat {
+                //Lighting is calculated here, as everything after takes the camera into account. Lighting should not be camera dependent.
+                let lighting_a = calculate_lighting(pose_a, pose_b, pose_c, &scene.lights, &mesh.material, eye);
+
+                // Textured and vertex colored triangles still need a look up per pixel, the rest is one solid color
+                if mesh.material.texture.is_some() || !mesh.colors.is_empty() {
+                    for vertex in triangle.iter_mut() {
+                        vertex.varyings.color = lighting_a.diffuse;
+                        vertex.varyings.specular = lighting_a.specular;
+                    }
+                } else {
+                    let mut lighting = color_to_rgba(lighting_a.color([1.0, 1.0, 1.0]), mesh.material.opacity);
+                    if highlighted {
+                        lighting = highlight_color(lighting);
+                    }
+
+                    // Cut away whatever is outside the view, the rest may have become a polygon
+                    let polygon = clip_triangle(triangle);
+                    let screen: Vec<[f32; 3]> = polygon
+                        .iter()
+                        .map(|vertex| clip_to_screen(vertex.position, canvas_width, canvas_height))
+                        .collect();
+
+                    for j in 1..screen.len().saturating_sub(1) {
+                        framebuffer.draw_triangle([screen[0], screen[j], screen[j + 1]], lighting);
+                    }
+                    continue;
+                }
+            } else {
+                // Smooth shading uses the vertex normals, meshes without any fall back to the face normal
+                let face_normal = calculate_normal(pose_a, pose_b, pose_c);
+                for (vertex, index) in triangle.iter_mut().zip([a, b, c]) {
+                    let normal = match mesh.normals.get(index) {
+                        Some(normal) => vec3_normalize(mat4_transform_direction(&normal_matrix, [normal.0, normal.1, normal.2])),
+                        None => face_normal,
+                    };
+                    vertex.varyings.normal = normal;
+
+                    if shading == Shading::Gouraud {
+                        let lighting = calculate_point_lighting(vertex.varyings.world, normal, &scene.lights, &mesh.material, eye);
+                        vertex.varyings.color = lighting.diffuse;
+                        vertex.varyings.specular = lighting.specular;
+                    }
+                }
+            }
+
+            let polygon = clip_triangle(triangle);
+            let screen: Vec<ScreenVertex> = polygon
+                .iter()
+                .map(|vertex| ScreenVertex {
+                    position: clip_to_screen(vertex.position, canvas_width, canvas_height),
+                    inv_w: 1.0 / vertex.position[3],
+                    varyings: vertex.varyings,
+                })
+                .collect();
+
+            for j in 1..screen.len().saturating_sub(1) {
+                framebuffer.draw_shaded_triangle([screen[0], screen[j], screen[j + 1]], |varyings| {
+                    let color = shade_pixel(scene, &mesh.material, eye, shading, varyings);
+                    if highlighted {
+                        highlight_color(color)
+                    } else {
+                        color
+                    }
+                });
+            }
+        }
+    }
+
+    stats
+}
+
+// Color of a single pixel for the smooth shading modes and textured or vertex colored flat triangles
+fn shade_pixel(scene: &Scene, material: &Material, eye: Eye, shading: Shading, varyings: &Varyings) -> [u8; 4] {
+    let lighting = match shading {
+        Shading::Phong => {
+            // Interpolated normals come out shorter than 1, so they need normalizing again
+            let normal = vec3_normalize(varyings.normal);
+            calculate_point_lighting(varyings.world, normal, &scene.lights, material, eye)
+        }
+        _ => Illumination { diffuse: varyings.color, specular: varyings.specular },
+    };
+
+    let mut texel = match &material.texture {
+        Some(texture) => texture.sample(varyings.uv, material.texture_filter),
+        None => [1.0, 1.0, 1.0],
+    };
+    for (channel, tint) in texel.iter_mut().zip(varyings.tint) {
+        *channel *= tint;
+    }
+
+    color_to_rgba(lighting.color(texel), material.opacity)
+}
+
+fn highlight_color(color: [u8; 4]) -> [u8; 4] {
+    let mut result = color;
+    for (channel, target) in result.iter_mut().zip(HIGHLIGHT_COLOR) {
+        *channel = (*channel as f32 + (target - *channel as f32) * HIGHLIGHT_AMOUNT) as u8;
+    }
+    result
+}
+
+// Checks the winding of a world space triangle as the camera sees it
+fn is_culled(scene: &Scene, cull_mode: CullMode, pose_a: [f32; 3], pose_b: [f32; 3], pose_c: [f32; 3]) -> bool {
+    if cull_mode == CullMode::Off {
+        return false;
+    }
+
+    let normal = calculate_normal(pose_a, pose_b, pose_c);
+    let view_direction = match scene.camera.projection {
+        Projection::Perspective => vec3_sub(pose_a, scene.camera.position),
+        Projection::Orthographic => scene.camera.forward(),
+    };
+
+    // Normal pointing away from the camera means the triangle shows up clockwise
+    let facing = vec3_dot(normal, view_direction);
+    match cull_mode {
+        CullMode::Off => false,
+        CullMode::Clockwise => facing > 0.0,
+        CullMode::CounterClockwise => facing < 0.0,
+    }
+}
+
+// Perspective divide and viewport transform. Depth is mapped to 0 (near) .. 1 (far),
+// which interpolates linearly across the screen for both projections.
+fn clip_to_screen(clip: [f32; 4], canvas_width: f32, canvas_height: f32) -> [f32; 3] {
+    let ndc = [clip[0] / clip[3], clip[1] / clip[3], clip[2] / clip[3]];
+    [
+        (ndc[0] * 0.5 + 0.5) * canvas_width,
+        (0.5 - ndc[1] * 0.5) * canvas_height,
+        ndc[2] * 0.5 + 0.5,
+    ]
+}
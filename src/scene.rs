@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+use crate::animation::{LightAnimation, ObjectAnimation};
+use crate::camera::Camera;
+use crate::import::{import_mesh, ImportSource};
+use crate::material::Material;
+use crate::math::{mat4_mul, Mat4};
+use crate::transform::Transform;
+
+pub struct Scene {
+    pub camera: Camera,
+    pub objects: Vec<Mesh>,
+    pub lights: Vec<Light>,
+    // Seconds the timeline runs for before it stops or loops
+    pub animation_length: f32,
+}
+
+pub const DEFAULT_ANIMATION_LENGTH: f32 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LightKind {
+    // Shines in every direction from a position and fades with distance
+    Point,
+    // Infinitely far away, like the sun. Only the direction matters
+    Directional,
+    // A point light limited to a cone
+    Spot,
+    // Lights everything evenly
+    Ambient,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Light {
+    pub kind: LightKind,
+    // Point and spot lights
+    pub position: [f32; 3],
+    // The way the light travels, directional and spot lights
+    pub direction: [f32; 3],
+    // RGB, 0 to 1
+    pub color: [f32; 3],
+    pub intensity: f32,
+    // Constant, linear and quadratic falloff with distance, point and spot lights
+    pub attenuation: [f32; 3],
+    // Spot cone in degrees from the center, full brightness inside `inner_angle`
+    // fading out to nothing at `outer_angle`
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    #[serde(default)]
+    pub animation: LightAnimation,
+}
+
+impl Light {
+    // A white light of the given kind with some sensible starting values
+    pub fn new(kind: LightKind) -> Self {
+        let intensity = match kind {
+            LightKind::Point | LightKind::Spot => 30.0,
+            LightKind::Directional => 0.8,
+            LightKind::Ambient => 0.1,
+        };
+
+        Light {
+            kind,
+            position: [4.8, 5.1, 4.5],
+            direction: [-0.5, -1.0, -0.5],
+            color: [1.0, 1.0, 1.0],
+            intensity,
+            attenuation: [0.0, 0.0, 1.0],
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+            animation: LightAnimation::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mesh {
+    pub name: String,
+    pub vertices: Vec<(f32, f32, f32)>,
+    // One per vertex, used for smooth shading
+    #[serde(default)]
+    pub normals: Vec<(f32, f32, f32)>,
+    // Texture coordinates, one per vertex. Empty when the mesh has none
+    #[serde(default)]
+    pub uvs: Vec<(f32, f32)>,
+    // RGB from 0 to 1, one per vertex. Tints the material like a texture, empty when the mesh has none
+    #[serde(default)]
+    pub colors: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+    // Polygons in the file the mesh came from, before they were cut into triangles
+    #[serde(default)]
+    pub face_count: usize,
+    // Relative to the parent when there is one
+    #[serde(default)]
+    pub transform: Transform,
+    // Index in `Scene::objects` of the object this one is attached to
+    #[serde(default)]
+    pub parent: Option<usize>,
+    #[serde(default)]
+    pub cull_mode: CullMode,
+    #[serde(default)]
+    pub material: Material,
+    #[serde(default)]
+    pub animation: ObjectAnimation,
+}
+
+impl Mesh {
+    // Object to parent space
+    pub fn local_matrix(&self) -> Mat4 {
+        self.transform.matrix()
+    }
+}
+
+// Which screen space winding gets skipped. OBJ files wind their front faces
+// counter-clockwise, so culling clockwise triangles removes the back faces.
+// Off unless asked for, open and mirrored meshes would lose faces otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum CullMode {
+    #[default]
+    Off,
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            camera: Camera::default(),
+            objects: vec![
+                bundled_mesh("suzanne.obj", include_bytes!("models/suzanne.obj"), [1.6, 0.7, -1.3], "Suzanne"), 
+                bundled_mesh("mario.obj", include_bytes!("models/mario.obj"), [0.0, 0.0, 0.0], "Mario")
+                ],
+            lights: vec![
+                Light {intensity: 29.3, ..Light::new(LightKind::Point)},
+                Light {intensity: 0.08, ..Light::new(LightKind::Ambient)},
+                ],
+            animation_length: DEFAULT_ANIMATION_LENGTH,
+        }
+    }
+}
+
+impl Scene {
+    // Object to world space for every object, each parent's matrix applied on top of its children's
+    pub fn world_matrices(&self) -> Vec<Mat4> {
+        let mut matrices: Vec<Option<Mat4>> = vec![None; self.objects.len()];
+
+        for index in 0..self.objects.len() {
+            // Climb up to the first object that's done already or has no parent, then work back down
+            let mut chain = vec![index];
+            while let Some(parent) = self.objects[*chain.last().unwrap()].parent {
+                if parent >= self.objects.len() || matrices[parent].is_some() || chain.contains(&parent) {
+                    break;
+                }
+                chain.push(parent);
+            }
+
+            for &object in chain.iter().rev() {
+                let local = self.objects[object].local_matrix();
+                let parent = self.objects[object].parent.and_then(|parent| matrices.get(parent).copied().flatten());
+                matrices[object] = Some(match parent {
+                    Some(parent) => mat4_mul(&parent, &local),
+                    None => local,
+                });
+            }
+        }
+
+        matrices.into_iter().map(|matrix| matrix.unwrap()).collect()
+    }
+
+    pub fn world_matrix(&self, index: usize) -> Mat4 {
+        let mut matrix = self.objects[index].local_matrix();
+        let mut current = index;
+        for _ in 0..self.objects.len() {
+            let Some(parent) = self.objects[current].parent.filter(|parent| *parent < self.objects.len()) else {
+                break;
+            };
+            matrix = mat4_mul(&self.objects[parent].local_matrix(), &matrix);
+            current = parent;
+        }
+        matrix
+    }
+
+    // How many parents are above an object, None when following them goes in a circle or off the end
+    pub fn depth(&self, index: usize) -> Option<usize> {
+        let mut current = index;
+        for depth in 0..=self.objects.len() {
+            match self.objects.get(current)?.parent {
+                Some(parent) => current = parent,
+                None => return Some(depth),
+            }
+        }
+        None
+    }
+
+    // Whether `ancestor` is somewhere above `index`
+    pub fn is_descendant(&self, index: usize, ancestor: usize) -> bool {
+        let mut current = index;
+        for _ in 0..self.objects.len() {
+            match self.objects.get(current).and_then(|mesh| mesh.parent) {
+                Some(parent) if parent == ancestor => return true,
+                Some(parent) => current = parent,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    pub fn children(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.objects.len()).filter(move |child| self.objects[*child].parent == Some(index))
+    }
+
+    // Every object, each parent followed by its children
+    pub fn tree_order(&self) -> Vec<usize> {
+        let mut order = vec![];
+        let mut stack: Vec<usize> = (0..self.objects.len()).rev().filter(|index| self.objects[*index].parent.is_none()).collect();
+
+        while let Some(index) = stack.pop() {
+            order.push(index);
+            let children: Vec<usize> = self.children(index).collect();
+            stack.extend(children.into_iter().rev());
+        }
+        order
+    }
+
+    // Parent indices past `index` move down by one. Children of the removed object are left
+    // without a parent, so attach them somewhere else first to keep them where they are.
+    pub fn remove_object(&mut self, index: usize) -> Mesh {
+        let mut mesh = self.objects.remove(index);
+        for object in self.objects.iter_mut().chain(std::iter::once(&mut mesh)) {
+            object.parent = match object.parent {
+                Some(parent) if parent == index => None,
+                Some(parent) if parent > index => Some(parent - 1),
+                parent => parent,
+            };
+        }
+        mesh
+    }
+
+    // The other way around from `remove_object`, so removing and inserting again gives back the same scene
+    pub fn insert_object(&mut self, index: usize, mut mesh: Mesh) {
+        for object in self.objects.iter_mut().chain(std::iter::once(&mut mesh)) {
+            object.parent = object.parent.map(|parent| if parent >= index { parent + 1 } else { parent });
+        }
+        self.objects.insert(index, mesh);
+    }
+}
+
+// The models baked into the binary always come back as a single mesh, they can't find their materials
+fn bundled_mesh(file_name: &str, bytes: &[u8], position: [f32; 3], name: &str) -> Mesh {
+    let mut mesh = import_mesh(ImportSource::Bytes { name: file_name, bytes })
+        .expect("bundled models are valid")
+        .remove(0);
+
+    mesh.name = name.to_string();
+    mesh.transform.translation = position;
+    mesh
+}
@@ -0,0 +1,271 @@
+// Saving and opening whole scenes. Scene files are JSON with the geometry and textures
+// stored inside, so a file still opens when the models it was built from are gone.
+// Every file says which version of the format it was written in, older ones get
+// migrated step by step when they are opened.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::animation::Animated;
+use crate::camera::{Camera, CameraMode};
+use crate::gizmo::{GizmoMode, GizmoSpace, Snapping};
+use crate::normals::{generate_normals, DEFAULT_CREASE_ANGLE};
+use crate::render::Shading;
+use crate::scene::{Light, Mesh, Scene, DEFAULT_ANIMATION_LENGTH};
+use crate::transform::{EulerOrder, Transform};
+
+// Bump this and add a step to `migrate` whenever the format changes
+pub const SCENE_VERSION: u64 = 4;
+
+// What the viewer was doing besides showing the scene, saved along with it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewState {
+    pub selected_object: Option<usize>,
+    // The object that spins and how fast around each axis
+    pub rotation_index: Option<usize>,
+    pub rotation_speed: [f32; 3],
+    pub shading: Shading,
+    pub euler_order: EulerOrder,
+    pub camera_mode: CameraMode,
+    pub gizmo_mode: GizmoMode,
+    pub gizmo_space: GizmoSpace,
+    pub snapping: Snapping,
+    // Where the timeline was and whether it loops
+    pub animation_time: f32,
+    pub looping: bool,
+}
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(io::Error),
+    // Not JSON, or JSON that doesn't look like a scene
+    Format(serde_json::Error),
+    // Written by a newer build that knows things this one doesn't
+    NewerVersion(u64),
+    // Parsed fine, but using it would go wrong later
+    Invalid(String),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io(err) => write!(f, "{}", err),
+            SceneFileError::Format(err) => write!(f, "not a valid scene file: {}", err),
+            SceneFileError::NewerVersion(version) => write!(
+                f,
+                "the file is version {}, this build only opens up to version {}",
+                version, SCENE_VERSION
+            ),
+            SceneFileError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+impl From<io::Error> for SceneFileError {
+    fn from(err: io::Error) -> Self {
+        SceneFileError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SceneFileError {
+    fn from(err: serde_json::Error) -> Self {
+        SceneFileError::Format(err)
+    }
+}
+
+// Borrows everything so saving doesn't have to copy the meshes
+#[derive(Serialize)]
+struct SceneFileRef<'a> {
+    version: u64,
+    camera: &'a Camera,
+    lights: &'a [Light],
+    objects: &'a [Mesh],
+    animation_length: f32,
+    view: &'a ViewState,
+}
+
+#[derive(Deserialize)]
+struct SceneFile {
+    #[serde(default)]
+    camera: Camera,
+    #[serde(default)]
+    lights: Vec<Light>,
+    #[serde(default)]
+    objects: Vec<Mesh>,
+    #[serde(default = "default_animation_length")]
+    animation_length: f32,
+    #[serde(default)]
+    view: ViewState,
+}
+
+pub fn save_scene(path: &Path, scene: &Scene, view: &ViewState) -> Result<(), SceneFileError> {
+    std::fs::write(path, scene_to_string(scene, view)?)?;
+    Ok(())
+}
+
+pub fn scene_to_string(scene: &Scene, view: &ViewState) -> Result<String, SceneFileError> {
+    let file = SceneFileRef {
+        version: SCENE_VERSION,
+        camera: &scene.camera,
+        lights: &scene.lights,
+        objects: &scene.objects,
+        animation_length: scene.animation_length,
+        view,
+    };
+
+    Ok(serde_json::to_string(&file)?)
+}
+
+pub fn load_scene(path: &Path) -> Result<(Scene, ViewState), SceneFileError> {
+    scene_from_bytes(&std::fs::read(path)?)
+}
+
+pub fn scene_from_bytes(bytes: &[u8]) -> Result<(Scene, ViewState), SceneFileError> {
+    let value: Value = serde_json::from_slice(bytes)?;
+
+    let Some(version) = value.get("version").and_then(Value::as_u64) else {
+        return Err(SceneFileError::Invalid("not a scene file, it has no version".to_string()));
+    };
+    if version > SCENE_VERSION {
+        return Err(SceneFileError::NewerVersion(version));
+    }
+
+    let file: SceneFile = serde_json::from_value(migrate(value, version))?;
+
+    check_camera(&file.camera)?;
+
+    let mut objects = file.objects;
+    for mesh in &mut objects {
+        check_mesh(mesh)?;
+    }
+
+    // The timeline clamps the time to 0..length and divides by the length
+    if !file.animation_length.is_finite() || file.animation_length <= 0.0 {
+        return Err(SceneFileError::Invalid(format!(
+            "the animation is {} seconds long, it has to be longer than 0",
+            file.animation_length
+        )));
+    }
+
+    let mut scene = Scene {
+        camera: file.camera,
+        objects,
+        lights: file.lights,
+        animation_length: file.animation_length,
+    };
+
+    // Hand edited keyframes can be out of order
+    let mut animated = std::iter::once(&mut scene.camera as &mut dyn Animated)
+        .chain(scene.lights.iter_mut().map(|light| light as &mut dyn Animated))
+        .chain(scene.objects.iter_mut().map(|mesh| mesh as &mut dyn Animated));
+    if !animated.all(|animated| animated.sort_keys()) {
+        return Err(SceneFileError::Invalid("a keyframe has a time that isn't a number".to_string()));
+    }
+
+    // Parents have to exist and can't end up being their own ancestors
+    if let Some(index) = (0..scene.objects.len()).find(|index| scene.depth(*index).is_none()) {
+        return Err(SceneFileError::Invalid(format!(
+            "{} is attached to an object that isn't there or is attached to it in turn",
+            scene.objects[index].name
+        )));
+    }
+    Ok((scene, file.view))
+}
+
+// Brings a file from `version` up to `SCENE_VERSION`. Every format change adds a step here
+// so old files keep opening.
+fn migrate(mut value: Value, version: u64) -> Value {
+    // Version 2 added parents. There is nothing to convert, older files have no hierarchy, but
+    // older builds would silently drop it.
+
+    // Version 3 put the position and Euler rotation of objects into a transform that can also scale.
+    // Some builds wrote transforms as version 2, objects that already have one are left alone.
+    if version < 3 {
+        if let Some(objects) = value.get_mut("objects").and_then(Value::as_array_mut) {
+            let old_objects = objects
+                .iter_mut()
+                .filter_map(Value::as_object_mut)
+                .filter(|object| !object.contains_key("transform"));
+            old_objects.for_each(|object| {
+                let mut read = |key: &str| -> [f32; 3] {
+                    object.remove(key).and_then(|value| serde_json::from_value(value).ok()).unwrap_or_default()
+                };
+
+                let mut transform = Transform::from_translation(read("position"));
+                transform.set_euler(read("rotation"), EulerOrder::Xyz);
+                object.insert("transform".to_string(), serde_json::json!(transform));
+            });
+        }
+    }
+
+    // Version 4 added keyframes and the animation length. There is nothing to convert, older
+    // files just have no animation, but older builds would silently drop it.
+
+    value
+}
+
+fn default_animation_length() -> f32 {
+    DEFAULT_ANIMATION_LENGTH
+}
+
+// A camera outside of these limits renders nothing but NaN
+fn check_camera(camera: &Camera) -> Result<(), SceneFileError> {
+    let finite = camera
+        .position
+        .iter()
+        .chain(&camera.rotation)
+        .chain([&camera.fov, &camera.ortho_height, &camera.near, &camera.far])
+        .all(|value| value.is_finite());
+    if !finite {
+        return Err(SceneFileError::Invalid("the camera has a setting that isn't a number".to_string()));
+    }
+
+    if camera.near <= 0.0 || camera.far <= camera.near {
+        return Err(SceneFileError::Invalid(format!(
+            "the camera sees from {} to {}, it has to start past 0 and end after it starts",
+            camera.near, camera.far
+        )));
+    }
+
+    Ok(())
+}
+
+// Hand edited files can have meshes the renderer would trip over
+fn check_mesh(mesh: &mut Mesh) -> Result<(), SceneFileError> {
+    let count = mesh.vertices.len();
+
+    if !mesh.indices.len().is_multiple_of(3) {
+        return Err(SceneFileError::Invalid(format!("{} has a triangle with less than 3 corners", mesh.name)));
+    }
+    if let Some(index) = mesh.indices.iter().find(|index| **index as usize >= count) {
+        return Err(SceneFileError::Invalid(format!(
+            "{} uses vertex {}, there are only {}",
+            mesh.name, index, count
+        )));
+    }
+
+    if mesh.face_count == 0 {
+        mesh.face_count = mesh.indices.len() / 3;
+    }
+
+    // Attributes that don't cover every vertex are dropped, normals can be worked out again
+    if mesh.uvs.len() != count {
+        mesh.uvs.clear();
+    }
+    if mesh.colors.len() != count {
+        mesh.colors.clear();
+    }
+    if mesh.normals.len() != count {
+        mesh.normals.clear();
+        generate_normals(mesh, DEFAULT_CREASE_ANGLE);
+    }
+
+    Ok(())
+}
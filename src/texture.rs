@@ -0,0 +1,138 @@
+// Image textures that get wrapped around meshes using their uv coordinates.
+// Texels are kept as RGB floats from 0 to 1 so sampling doesn't have to convert anything.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum TextureFilter {
+    // Takes the closest texel, blocky up close
+    Nearest,
+    // Blends the four closest texels
+    #[default]
+    Bilinear,
+}
+
+// Saved as 8 bit RGB, which is all the image files it came from had anyway
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "EncodedTexture", try_from = "EncodedTexture")]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    // Row by row, starting at the top of the image
+    pub texels: Vec<[f32; 3]>,
+}
+
+impl Texture {
+    // Decodes a PNG or JPEG file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Texture, String> {
+        let image = image::load_from_memory(bytes)
+            .map_err(|err| format!("Error loading texture: {}", err))?
+            .to_rgb8();
+
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err("Error loading texture: image is empty".to_string());
+        }
+
+        let texels = image
+            .pixels()
+            .map(|pixel| pixel.0.map(|channel| channel as f32 / 255.0))
+            .collect();
+
+        Ok(Texture {
+            width: width as usize,
+            height: height as usize,
+            texels,
+        })
+    }
+
+    // Color at the given uv. The texture repeats outside of 0..1 and
+    // v goes up the image like in OBJ files.
+    pub fn sample(&self, uv: [f32; 2], filter: TextureFilter) -> [f32; 3] {
+        let x = uv[0].rem_euclid(1.0) * self.width as f32;
+        let y = (1.0 - uv[1]).rem_euclid(1.0) * self.height as f32;
+
+        match filter {
+            TextureFilter::Nearest => self.texel(x.floor() as isize, y.floor() as isize),
+            TextureFilter::Bilinear => {
+                // Texel centers sit at +0.5, so shift back to blend between the right neighbours
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let tx = x - x0;
+                let ty = y - y0;
+                let (x0, y0) = (x0 as isize, y0 as isize);
+
+                let top = lerp3(self.texel(x0, y0), self.texel(x0 + 1, y0), tx);
+                let bottom = lerp3(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), tx);
+                lerp3(top, bottom, ty)
+            }
+        }
+    }
+
+    // Texel lookup that wraps around the edges
+    fn texel(&self, x: isize, y: isize) -> [f32; 3] {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        self.texels[y * self.width + x]
+    }
+}
+
+fn lerp3(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+// How a texture looks in a scene file, the texels packed into base64 so they don't take up a line each
+#[derive(Serialize, Deserialize)]
+struct EncodedTexture {
+    width: usize,
+    height: usize,
+    rgb: String,
+}
+
+impl From<Texture> for EncodedTexture {
+    fn from(texture: Texture) -> Self {
+        let bytes: Vec<u8> = texture
+            .texels
+            .iter()
+            .flat_map(|texel| texel.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect();
+
+        EncodedTexture {
+            width: texture.width,
+            height: texture.height,
+            rgb: BASE64.encode(bytes),
+        }
+    }
+}
+
+impl TryFrom<EncodedTexture> for Texture {
+    type Error = String;
+
+    fn try_from(encoded: EncodedTexture) -> Result<Self, Self::Error> {
+        let bytes = BASE64.decode(&encoded.rgb).map_err(|err| format!("texture data is broken: {}", err))?;
+        // The size comes from the file and can be anything, so it can't be trusted to multiply out
+        let size = encoded.width.checked_mul(encoded.height).and_then(|texels| texels.checked_mul(3));
+        if encoded.width == 0 || encoded.height == 0 || size != Some(bytes.len()) {
+            return Err(format!("texture data doesn't fit a {}x{} image", encoded.width, encoded.height));
+        }
+
+        let texels = bytes
+            .chunks_exact(3)
+            .map(|texel| [texel[0], texel[1], texel[2]].map(|channel| channel as f32 / 255.0))
+            .collect();
+
+        Ok(Texture {
+            width: encoded.width,
+            height: encoded.height,
+            texels,
+        })
+    }
+}
@@ -0,0 +1,137 @@
+// Where an object is, how it is turned and how big it is, relative to its parent.
+// The rotation is a quaternion so spinning around several axes at once never locks up,
+// the settings show it as Euler angles in whichever order is picked.
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    pub rotation: Quat,
+    // Per axis, negative values mirror the object
+    pub scale: [f32; 3],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: [0.0, 0.0, 0.0],
+            rotation: quat_identity(),
+            scale: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: [f32; 3]) -> Self {
+        Transform { translation, ..Transform::default() }
+    }
+
+    // Scaled first, then rotated, then moved
+    pub fn matrix(&self) -> Mat4 {
+        mat4_mul(
+            &mat4_translation(self.translation),
+            &mat4_mul(&quat_to_mat4(self.rotation), &mat4_scale(self.scale)),
+        )
+    }
+
+    // Shear can't be stored and gets lost, like in `mat4_decompose`
+    pub fn from_matrix(m: &Mat4) -> Self {
+        let (translation, angles, scale) = mat4_decompose(m);
+        Transform {
+            translation,
+            rotation: quat_from_euler(angles, EulerOrder::Xyz),
+            scale,
+        }
+    }
+
+    // Degrees around x, y and z
+    pub fn euler(&self, order: EulerOrder) -> [f32; 3] {
+        quat_to_euler(self.rotation, order).map(f32::to_degrees)
+    }
+
+    pub fn set_euler(&mut self, degrees: [f32; 3], order: EulerOrder) {
+        self.rotation = quat_from_euler(degrees.map(f32::to_radians), order);
+    }
+}
+
+// Which axis is turned around first. Xyz turns around x, then y, then z, so its matrix is Rz * Ry * Rx.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum EulerOrder {
+    #[default]
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+impl EulerOrder {
+    pub const ALL: [EulerOrder; 6] = [
+        EulerOrder::Xyz,
+        EulerOrder::Xzy,
+        EulerOrder::Yxz,
+        EulerOrder::Yzx,
+        EulerOrder::Zxy,
+        EulerOrder::Zyx,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EulerOrder::Xyz => "XYZ",
+            EulerOrder::Xzy => "XZY",
+            EulerOrder::Yxz => "YXZ",
+            EulerOrder::Yzx => "YZX",
+            EulerOrder::Zxy => "ZXY",
+            EulerOrder::Zyx => "ZYX",
+        }
+    }
+
+    // Axis indices in the order they are applied
+    fn axes(self) -> [usize; 3] {
+        match self {
+            EulerOrder::Xyz => [0, 1, 2],
+            EulerOrder::Xzy => [0, 2, 1],
+            EulerOrder::Yxz => [1, 0, 2],
+            EulerOrder::Yzx => [1, 2, 0],
+            EulerOrder::Zxy => [2, 0, 1],
+            EulerOrder::Zyx => [2, 1, 0],
+        }
+    }
+}
+
+// Angles in radians around x, y and z, no matter which order they are applied in
+pub fn quat_from_euler(angles: [f32; 3], order: EulerOrder) -> Quat {
+    let mut rotation = quat_identity();
+    for axis in order.axes() {
+        let mut direction = [0.0; 3];
+        direction[axis] = 1.0;
+        rotation = quat_mul(quat_from_axis_angle(direction, angles[axis]), rotation);
+    }
+    rotation
+}
+
+// Reads the angles back out of the rotation matrix the order builds
+pub fn quat_to_euler(q: Quat, order: EulerOrder) -> [f32; 3] {
+    let m = quat_to_mat4(q);
+    let [i, j, k] = order.axes();
+    // The signs flip when the axes don't go round in x, y, z order
+    let sign = if j == (i + 1) % 3 { 1.0 } else { -1.0 };
+
+    let sin_j = (-sign * m[k][i]).clamp(-1.0, 1.0);
+    let mut angles = [0.0; 3];
+    angles[j] = sin_j.asin();
+
+    if sin_j.abs() < 0.9999 {
+        angles[i] = (sign * m[k][j]).atan2(m[k][k]);
+        angles[k] = (sign * m[j][i]).atan2(m[i][i]);
+    } else {
+        // Gimbal lock, the first and last axis turn around the same line so the last one takes all of it
+        angles[k] = (-sign * m[i][j]).atan2(m[j][j]);
+    }
+    angles
+}
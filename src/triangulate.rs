@@ -0,0 +1,125 @@
+// Cuts polygons with any number of corners into triangles.
+// Convex polygons are fanned out from their first corner, concave ones go through ear clipping.
+
+use crate::math::{vec3_cross, vec3_dot, vec3_sub};
+
+// Returns the triangles as indices into `points`, wound the same way as the polygon.
+// Polygons with less than 3 corners give nothing back.
+pub fn triangulate(points: &[[f32; 3]]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return vec![];
+    }
+
+    let normal = polygon_normal(points);
+
+    if points.len() == 3 || is_convex(points, normal) {
+        return fan(points.len());
+    }
+
+    ear_clip(points, normal).unwrap_or_else(|| fan(points.len()))
+}
+
+fn fan(count: usize) -> Vec<[usize; 3]> {
+    (1..count - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+// Newell's method, works for concave and slightly non planar polygons.
+// The length isn't normalized, only the direction matters here.
+fn polygon_normal(points: &[[f32; 3]]) -> [f32; 3] {
+    let mut normal = [0.0, 0.0, 0.0];
+    for (i, current) in points.iter().enumerate() {
+        let next = points[(i + 1) % points.len()];
+        normal[0] += (current[1] - next[1]) * (current[2] + next[2]);
+        normal[1] += (current[2] - next[2]) * (current[0] + next[0]);
+        normal[2] += (current[0] - next[0]) * (current[1] + next[1]);
+    }
+    normal
+}
+
+// How much the polygon turns at `corner`, positive for a turn the same way as its winding
+fn turn(previous: [f32; 3], corner: [f32; 3], next: [f32; 3], normal: [f32; 3]) -> f32 {
+    vec3_dot(vec3_cross(vec3_sub(corner, previous), vec3_sub(next, corner)), normal)
+}
+
+fn is_convex(points: &[[f32; 3]], normal: [f32; 3]) -> bool {
+    let count = points.len();
+    (0..count).all(|i| {
+        let previous = points[(i + count - 1) % count];
+        let next = points[(i + 1) % count];
+        turn(previous, points[i], next, normal) >= 0.0
+    })
+}
+
+// Repeatedly cuts off a convex corner that has no other corner inside it.
+// Gives up on polygons that are too broken to find an ear in, like self intersecting ones.
+fn ear_clip(points: &[[f32; 3]], normal: [f32; 3]) -> Option<Vec<[usize; 3]>> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let previous = remaining[(i + count - 1) % count];
+            let corner = remaining[i];
+            let next = remaining[(i + 1) % count];
+            let triangle = [points[previous], points[corner], points[next]];
+
+            if turn(triangle[0], triangle[1], triangle[2], normal) <= 0.0 {
+                return false;
+            }
+
+            remaining
+                .iter()
+                .filter(|&&other| other != previous && other != corner && other != next)
+                .all(|&other| !inside_triangle(points[other], triangle, normal))
+        })?;
+
+        let previous = remaining[(ear + count - 1) % count];
+        let next = remaining[(ear + 1) % count];
+        triangles.push([previous, remaining[ear], next]);
+        remaining.remove(ear);
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    Some(triangles)
+}
+
+// Points on the edge count as inside, so an ear never touches another corner
+fn inside_triangle(point: [f32; 3], triangle: [[f32; 3]; 3], normal: [f32; 3]) -> bool {
+    let [a, b, c] = triangle;
+    turn(a, b, point, normal) >= 0.0 && turn(b, c, point, normal) >= 0.0 && turn(c, a, point, normal) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Twice the signed area in the xy plane, positive for counter-clockwise
+    fn area(points: &[[f32; 3]], triangle: [usize; 3]) -> f32 {
+        let [a, b, c] = triangle.map(|index| points[index]);
+        (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])
+    }
+
+    #[test]
+    fn too_few_corners_give_nothing() {
+        assert!(triangulate(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]]).is_empty());
+    }
+
+    #[test]
+    fn convex_polygons_are_fanned() {
+        let quad = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        assert_eq!(triangulate(&quad), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn concave_polygons_keep_their_winding_and_area() {
+        // The corner at (2, 1) points inwards, a fan from the first corner would fold over it
+        let points = [[0.0, 0.0, 0.0], [4.0, 0.0, 0.0], [4.0, 4.0, 0.0], [2.0, 1.0, 0.0], [0.0, 4.0, 0.0]];
+        let triangles = triangulate(&points);
+
+        assert_eq!(triangles.len(), 3);
+        assert!(triangles.iter().all(|triangle| area(&points, *triangle) > 0.0));
+        let total: f32 = triangles.iter().map(|triangle| area(&points, *triangle)).sum();
+        assert!((total - 20.0).abs() < 1e-4);
+    }
+}